@@ -1,202 +1,5509 @@
+#![cfg_attr(test, allow(clippy::unnecessary_cast))]
+
 use std::{
     any::{Any, TypeId},
-    collections::{hash_map::RandomState, HashMap},
+    collections::{hash_map::{DefaultHasher, RandomState}, HashMap, HashSet, VecDeque},
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    ops::{Bound, Range, RangeBounds},
+};
+#[cfg(feature = "debug-tracking")]
+use std::{
+    cell::Cell,
+    ops::{Deref, DerefMut},
 };
 
+/// Create a [`TypePools`] containing the given values for each type
+///
+/// ```
+/// # use type_pools::type_pools;
+/// let pools = type_pools! {
+///     u32 => [1, 2, 3],
+///     &str => ["a", "b"],
+/// };
+/// assert_eq!(pools.len::<u32>(), Some(3));
+/// ```
+#[macro_export]
+macro_rules! type_pools {
+    ($($ty:ty => [$($value:expr),* $(,)?]),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut pools = $crate::TypePools::new();
+        $(pools.extend::<$ty, _>([$($value),*]);)*
+        pools
+    }};
+}
+
 /// A collection of pools or arrays that contain values of a specific type
 pub struct TypePools<H = RandomState> {
     pools: HashMap<TypeId, Box<dyn TypePoolTrait>, H>,
+    /// `Observers<T>` for each type that has callbacks registered
+    observers: HashMap<TypeId, Box<dyn Any>>,
+    /// Maximum amount of type pools, see [`TypePools::set_max_types`]
+    max_types: Option<usize>,
+    /// Incremented on every push, used to find the least recently pushed to pool
+    touch_clock: u64,
+    /// Threshold of emptied pools for pruning, see [`TypePools::set_auto_prune`]
+    auto_prune: Option<usize>,
+    /// The amount of empty type pools, updated whenever a type pool is added, removed, emptied
+    /// or filled. `None` if it has to be counted again, because mutable access to the type pools
+    /// was handed out
+    empty_types: Option<usize>,
+    /// Recorded events, `Some` if recording is enabled
+    events: Option<Vec<PoolEvent>>,
+    /// How type pools grow on `push` and `extend`, see [`TypePools::set_growth_policy`]
+    growth_policy: GrowthPolicy,
+    /// Capacity of type pools that don't exist yet, see [`TypePools::hint_capacity`]
+    capacity_hints: HashMap<TypeId, usize>,
+    /// The amount of live borrows handed out by the tracked accessors
+    #[cfg(feature = "debug-tracking")]
+    borrows: Cell<usize>,
+}
+
+impl Default for TypePools {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TypePools {
     pub fn new() -> Self {
-        TypePools { pools: HashMap::new() }
+        TypePools {
+            pools: HashMap::new(),
+            observers: HashMap::new(),
+            max_types: None,
+            touch_clock: 0,
+            auto_prune: None,
+            empty_types: Some(0),
+            events: None,
+            growth_policy: GrowthPolicy::Amortized,
+            capacity_hints: HashMap::new(),
+            #[cfg(feature = "debug-tracking")]
+            borrows: Cell::new(0),
+        }
     }
 
-    /// Get a reference to a type pool
+    /// Create pools containing a single type pool with `values`
+    pub fn from_pool<T: 'static>(values: Vec<T>) -> Self {
+        TypePools::new().with_pool(values)
+    }
+
+    /// Create pools containing a single type pool with the values of `iter`
+    pub fn from_iter_single<T: 'static, I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut pools = TypePools::new();
+        pools.extend(iter);
+        pools
+    }
+
+    /// Add `values` to the type pool of `T`, returning the pools for chaining.
+    /// If the type pool doesn't exist yet, it will be created from `values` without copying
+    ///
+    /// The values are pushed like with [`push_all`](Self::push_all), so observers registered
+    /// with [`on_push`](Self::on_push) are called for each of them.
+    pub fn with_pool<T: 'static>(mut self, mut values: Vec<T>) -> Self {
+        if !self.observers.is_empty() {
+            values.iter().for_each(|value| self.notify_push(value));
+        }
+        self.touch_clock += 1;
+        let touch = self.touch_clock;
+        let start = self.len_or_zero::<T>();
+        match self.deque_mut::<T>() {
+            Some(deque) => {
+                deque.values.extend(values);
+                deque.last_touch = touch;
+            }
+            None => {
+                let pool = self.pool_or_create::<T>();
+                if pool.values.is_empty() {
+                    pool.values = values;
+                } else {
+                    pool.values.append(&mut values);
+                }
+                pool.touch(touch);
+            }
+        }
+
+        let end = self.len_or_zero::<T>();
+        self.empty_changed(start == 0, end == 0);
+        if self.events.is_some() {
+            self.record::<T>(PoolEventKind::Push, start..end);
+        }
+        self
+    }
+
+    /// Reserve capacity for at least `n` values in the type pool of `T`, returning the pools
+    /// for chaining. If the type pool doesn't exist yet, it will be created
+    pub fn with_capacity_for<T: 'static>(mut self, n: usize) -> Self {
+        self.with_values_or_create::<T, _>(|values| values.reserve(n.saturating_sub(values.len())));
+        self
+    }
+
+    /// Decompose the pools into the map of type pools they are stored in.
+    ///
+    /// Registered observers are dropped.
+    pub fn into_inner(self) -> HashMap<TypeId, Box<dyn TypePoolTrait>> {
+        self.pools
+    }
+
+    /// Consume the pools, returning the values of the type pool of `T` and dropping all other
+    /// type pools. Returns an empty `Vec` if the type pool doesn't exist
+    pub fn into_values<T: 'static>(mut self) -> Vec<T> {
+        let Some(mut pool) = self.pools.remove(&TypeId::of::<T>()) else { return Vec::new() };
+        match TypeDeque::<T>::cast_mut(pool.as_mut()) {
+            Some(deque) => Vec::from(std::mem::take(&mut deque.values)),
+            None => TypePool::<T>::cast_mut(pool.as_mut())
+                .map(|pool| std::mem::take(&mut pool.values))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Construct pools from a map of type pools, as returned by [`into_inner`](Self::into_inner)
+    ///
+    /// # Panics
+    /// If a type pool is not stored under the `TypeId` of the type of its values
+    pub fn from_inner(pools: HashMap<TypeId, Box<dyn TypePoolTrait>>) -> Self {
+        for (id, pool) in pools.iter() {
+            assert!(*id == pool.value_type_id(), "type pool stored under the wrong TypeId");
+        }
+        TypePools {
+            touch_clock: pools.values().map(|pool| pool.last_touch()).max().unwrap_or(0),
+            pools,
+            empty_types: None,
+            ..TypePools::new()
+        }
+    }
+
+    /// Create new pools with an empty type pool for every type of `self`, each with at least
+    /// the capacity of the original type pool. Observers and settings are not cloned
+    pub fn clone_shape(&self) -> TypePools {
+        TypePools {
+            pools: self.pools.iter()
+                .map(|(id, pool)| (*id, pool.clone_empty_with_capacity()))
+                .collect(),
+            empty_types: Some(self.pools.len()),
+            ..TypePools::new()
+        }
+    }
+
+    /// Move all type pools and deques to new pools, leaving `self` without any types.
+    /// Observers and settings like [`set_max_types`](Self::set_max_types) stay with `self`
+    pub fn take_all(&mut self) -> TypePools {
+        TypePools {
+            pools: std::mem::take(&mut self.pools),
+            empty_types: self.empty_types.replace(0),
+            touch_clock: self.touch_clock,
+            ..TypePools::new()
+        }
+    }
+
+    /// Replace all type pools and deques with the ones of `new`, returning the old ones.
+    /// Observers and settings stay with `self`, the ones of `new` are dropped. If `new` has more
+    /// types than allowed by [`set_max_types`](Self::set_max_types), type pools are evicted
+    pub fn replace_all(&mut self, new: TypePools) -> TypePools {
+        let old = self.take_all();
+        self.pools = new.pools;
+        self.empty_types = new.empty_types;
+        self.touch_clock = self.touch_clock.max(new.touch_clock);
+        if let Some(max_types) = self.max_types {
+            self.evict_to(max_types);
+        }
+        old
+    }
+
+    /// The size in bytes of a single value of the type with `id`, or `None` if its type pool
+    /// doesn't exist
+    pub fn element_size(&self, id: TypeId) -> Option<usize> {
+        self.pools.get(&id)
+            .map(|pool| pool.element_size())
+    }
+
+    /// Get the type pool of the type with `id` as `Any`, which can be downcast to its
+    /// `TypePool<T>` unless the type is in deque mode
+    pub fn pool_as_any(&self, id: TypeId) -> Option<&dyn Any> {
+        self.pools.get(&id)
+            .map(|pool| pool.as_any())
+    }
+
+    /// Get the type pool of the type with `id` as mutable `Any`, which can be downcast to its
+    /// `TypePool<T>` unless the type is in deque mode
+    pub fn pool_as_any_mut(&mut self, id: TypeId) -> Option<&mut dyn Any> {
+        self.empty_types = None;
+        self.pools.get_mut(&id)
+            .map(|pool| pool.as_any_mut())
+    }
+
+    /// Get a read-only view of the pools
+    pub fn as_view(&self) -> TypePoolsRef<'_> {
+        TypePoolsRef { pools: self }
+    }
+
+    /// Get a reference to a type pool. Returns `None` if `T` is in deque mode, see
+    /// [`type_deque`](Self::type_deque)
     pub fn type_pool<T: 'static>(&self) -> Option<&TypePool<T>> {
         self.pools.get(&TypeId::of::<T>())
-            .map(|pool| {
-                unsafe { TypePool::<T>::cast_unchecked(pool.as_ref()) } // safety: we know the type is correct
+            .and_then(|pool| TypePool::<T>::cast(pool.as_ref()))
+    }
+
+    /// Get a reference to a type pool, checking that the stored pool really has the type
+    /// `TypePool<T>`. This can't fail unless an internal invariant was broken
+    ///
+    /// Returns `Ok(None)` if the type pool doesn't exist or `T` is in deque mode
+    pub fn try_type_pool<T: 'static>(&self) -> Result<Option<&TypePool<T>>, DowncastError> {
+        let Some(pool) = self.pools.get(&TypeId::of::<T>()) else { return Ok(None) };
+        if pool.as_any().is::<TypeDeque<T>>() {
+            return Ok(None);
+        }
+        pool.as_any()
+            .downcast_ref::<TypePool<T>>()
+            .map(Some)
+            .ok_or(DowncastError {
+                expected: std::any::type_name::<T>(),
+                found: pool.type_name(),
             })
     }
 
-    /// Get a mutable reference to a type pool
+    /// Get a mutable reference to a type pool. Returns `None` if `T` is in deque mode, see
+    /// [`type_deque_mut`](Self::type_deque_mut)
     pub fn type_pool_mut<T: 'static>(&mut self) -> Option<&mut TypePool<T>> {
+        self.empty_types = None;
+        self.pool_mut()
+    }
+
+    /// Get a mutable reference to a type pool. If the type pool doesn't exist yet, an empty one
+    /// will be created
+    ///
+    /// If `T` is in deque mode, its values are moved back to a type pool, which ends deque mode,
+    /// see [`make_deque`](Self::make_deque). The same holds for the other methods returning a
+    /// `TypePool<T>` that create the type pool if it doesn't exist.
+    pub fn get_or_create_pool<T: 'static>(&mut self) -> &mut TypePool<T> {
+        self.empty_types = None;
+        self.pool_or_create()
+    }
+
+    /// Like [`type_pool_mut`](Self::type_pool_mut), for callers that keep the count of empty type
+    /// pools up to date themselves
+    fn pool_mut<T: 'static>(&mut self) -> Option<&mut TypePool<T>> {
         self.pools.get_mut(&TypeId::of::<T>())
-            .map(|pool| {
-                unsafe { TypePool::<T>::cast_mut_unchecked(pool.as_mut()) } // safety: we know the type is correct
-            })
+            .and_then(|pool| TypePool::<T>::cast_mut(pool.as_mut()))
+    }
+
+    /// Like [`get_or_create_pool`](Self::get_or_create_pool), for callers that keep the count of
+    /// empty type pools up to date themselves
+    fn pool_or_create<T: 'static>(&mut self) -> &mut TypePool<T> {
+        if let Some(deque) = self.deque_mut::<T>() {
+            let pool = TypePool {
+                last_touch: deque.last_touch,
+                ..TypePool::from(Vec::from(std::mem::take(&mut deque.values)))
+            };
+            self.pools.insert(TypeId::of::<T>(), Box::new(pool));
+        } else if !self.pools.contains_key(&TypeId::of::<T>()) {
+            self.evict_for_new_type();
+            self.touch_clock += 1;
+            let mut pool = self.new_pool::<T>();
+            pool.last_touch = self.touch_clock;
+            self.pools.insert(TypeId::of::<T>(), Box::new(pool));
+            self.empty_changed(false, true);
+        }
+        let pool = unsafe { self.pools.get_mut(&TypeId::of::<T>()).unwrap_unchecked() }; // safety: the pool was created above if it didn't exist
+        unsafe { TypePool::<T>::cast_mut(pool.as_mut()).unwrap_unchecked() } // safety: a deque was replaced by a type pool above
+    }
+
+    /// Get a reference to a type pool. If the type pool doesn't exist yet, an empty one will
+    /// be created
+    pub fn type_pool_or_default<T: 'static>(&mut self) -> &TypePool<T> {
+        self.pool_or_create::<T>()
+    }
+
+    /// Call `f` with a mutable reference to a type pool, returning its result. If the type pool
+    /// doesn't exist yet, an empty one will be created
+    pub fn with_pool_mut<T: 'static, R, F: FnOnce(&mut TypePool<T>) -> R>(&mut self, f: F) -> R {
+        f(self.get_or_create_pool::<T>())
+    }
+
+    /// Get a mutable iterator over the type pool of `A` together with a reference to the
+    /// type pool of `B`
+    ///
+    /// Returns `None` if either type pool doesn't exist or if `A` and `B` are the same type
+    pub fn iter_mut_with_ref<A: 'static, B: 'static>(&mut self) -> Option<(impl Iterator<Item = &mut A>, &TypePool<B>)> {
+        if TypeId::of::<A>() == TypeId::of::<B>() {
+            return None;
+        }
+        let b = self.type_pool::<B>()? as *const TypePool<B>;
+        let a = self.pool_mut::<A>()?;
+        Some((a.values.iter_mut(), unsafe { &*b })) // safety: `A` and `B` are different types, so they are stored in different pools
+    }
+
+    /// Get a mutable reference to a type pool. If the type pool doesn't exist yet, it will be
+    /// created with the values returned by `f`
+    pub fn entry_or_insert_with<T: 'static, F: FnOnce() -> Vec<T>>(&mut self, f: F) -> &mut TypePool<T> {
+        if !self.pools.contains_key(&TypeId::of::<T>()) {
+            self.pool_or_create::<T>().values = f();
+            self.empty_changed(true, self.is_type_empty::<T>());
+            if self.events.is_some() {
+                self.record::<T>(PoolEventKind::Push, 0..self.len_or_zero::<T>());
+            }
+        }
+        self.get_or_create_pool::<T>()
+    }
+
+    /// Replace the values of the type pool with clones of `src`, reusing the allocation of the
+    /// type pool. If the type pool doesn't exist yet, it will be created
+    pub fn copy_from_slice_reusing<T: 'static + Clone>(&mut self, src: &[T]) {
+        let old_len = self.with_values_or_create::<T, _>(|values| {
+            let old_len = values.len();
+            values.clear();
+            values.extend_from_slice(src);
+            old_len
+        });
+
+        if self.events.is_some() {
+            self.record_replaced::<T>(old_len, src.len());
+        }
+    }
+
+    /// Combine `other_values` into the type pool using `combine`. If the type pool doesn't
+    /// exist yet, it will be created
+    pub fn merge_with<T: 'static, F: FnOnce(&mut Vec<T>, Vec<T>)>(&mut self, other_values: Vec<T>, combine: F) {
+        self.with_values_or_create::<T, _>(|values| combine(values, other_values));
+    }
+
+    /// Combine `other` into `self` by keeping, for every type, the type pool with the most
+    /// values. On equal lengths the type pool of `self` is kept
+    pub fn merge_preferring_longer(&mut self, other: TypePools) {
+        self.empty_types = None;
+        self.touch_clock = self.touch_clock.max(other.touch_clock);
+        for (id, pool) in other.pools {
+            match self.pools.get(&id) {
+                Some(existing) if existing.len() >= pool.len() => {}
+                Some(_) => { self.pools.insert(id, pool); }
+                None => {
+                    self.evict_for_new_type();
+                    self.pools.insert(id, pool);
+                }
+            }
+        }
+    }
+
+    /// Iterate over the values of the type pools of `T` in all `pools`, one collection after
+    /// the other
+    pub fn chain_iter<'a, T: 'static>(pools: &'a [&'a TypePools]) -> impl Iterator<Item = &'a T> {
+        pools.iter()
+            .filter_map(|pools| pools.iter_values::<T>())
+            .flatten()
+    }
+
+    /// Replace the values of the type pool with `values`, returning the old values. If the type
+    /// pool doesn't exist yet, it will be created with `values` and `None` is returned
+    ///
+    /// Observers registered with [`on_push`](Self::on_push) are called for each of `values`,
+    /// then the ones registered with [`on_remove`](Self::on_remove) for each old value.
+    pub fn set_values<T: 'static>(&mut self, values: Vec<T>) -> Option<Vec<T>> {
+        if !self.observers.is_empty() {
+            values.iter().for_each(|value| self.notify_push(value));
+        }
+        let existed = self.contains_type::<T>();
+        let new_len = values.len();
+        let old = self.with_values_or_create::<T, _>(|old| std::mem::replace(old, values));
+        if !existed {
+            self.record::<T>(PoolEventKind::Push, 0..new_len);
+            return None;
+        }
+
+        self.removed_all(PoolEventKind::Clear, &old, (!old.is_empty()).then_some(0).into_iter(), new_len == 0);
+        self.record::<T>(PoolEventKind::Push, 0..new_len);
+        Some(old)
+    }
+
+    /// Swap the values of the type pool with `other`. If the type pool doesn't exist yet, it
+    /// will be created
+    pub fn swap_values<T: 'static>(&mut self, other: &mut Vec<T>) {
+        self.with_values_or_create::<T, _>(|values| std::mem::swap(values, other));
+
+        if self.events.is_some() {
+            self.record_replaced::<T>(other.len(), self.len_or_zero::<T>());
+        }
+    }
+
+    /// Reserve exactly enough capacity for the type pool to hold `total` values, without
+    /// over-allocating. If the type pool doesn't exist yet, it will be created
+    pub fn reserve_exact_to<T: 'static>(&mut self, total: usize) {
+        self.with_values_or_create::<T, _>(|values| values.reserve_exact(total.saturating_sub(values.len())));
+    }
+
+    /// Reserve capacity for the type pool to hold as many values as it did at its peak. Values
+    /// pushed through references to the type pool, like the one returned by `type_pool_mut`,
+    /// only count once they are cleared or truncated, or the type pools are scanned again
+    pub fn reserve_to_peak<T: 'static>(&mut self) {
+        if let Some(pool) = self.pool_mut::<T>() {
+            let additional = pool.peak_len.saturating_sub(pool.values.len());
+            pool.values.reserve(additional);
+        }
+    }
+
+    /// Set the value at `idx`, first growing the type pool with default values if it is too
+    /// short. If the type pool doesn't exist yet, it will be created
+    ///
+    /// The default values and a `value` past the end are pushed like with
+    /// [`extend`](Self::extend), so observers registered with [`on_push`](Self::on_push) are
+    /// called for each of them.
+    pub fn overwrite_at<T: 'static + Default>(&mut self, idx: usize, value: T) {
+        let len = self.len_or_zero::<T>();
+        if len <= idx {
+            self.extend(std::iter::repeat_with(T::default).take(idx - len).chain([value]));
+            return;
+        }
+        if let Some(slot) = self.get_mut::<T>(idx) {
+            *slot = value;
+        }
+        if self.events.is_some() {
+            self.record::<T>(PoolEventKind::Set, idx..idx + 1);
+        }
+    }
+
+    /// Set the value at `idx`, returning the old value. If `idx` is the length of the type pool
+    /// the value is pushed, if it is larger the type pool is first grown with default values.
+    /// If the type pool doesn't exist yet, it will be created
+    ///
+    /// Growing the type pool notifies observers and records events like
+    /// [`overwrite_at`](Self::overwrite_at).
+    pub fn put<T: 'static + Default>(&mut self, idx: usize, value: T) -> Option<T> {
+        let len = self.len_or_zero::<T>();
+        if idx < len {
+            let old = self.get_mut::<T>(idx)
+                .map(|slot| std::mem::replace(slot, value));
+            if self.events.is_some() {
+                self.record::<T>(PoolEventKind::Set, idx..idx + 1);
+            }
+            return old;
+        }
+        self.overwrite_at(idx, value);
+        None
+    }
+
+    /// Replace the values of the type pool with `len` default values. If the type pool doesn't
+    /// exist yet, it will be created
+    pub fn init_defaults<T: 'static + Default + Clone>(&mut self, len: usize) {
+        let old_len = self.with_values_or_create::<T, _>(|values| {
+            let old_len = values.len();
+            values.clear();
+            values.resize(len, T::default());
+            old_len
+        });
+
+        if self.events.is_some() {
+            self.record_replaced::<T>(old_len, len);
+        }
+    }
+
+    /// Get mutable references to three different type pools at once
+    ///
+    /// # Panics
+    /// If any two of `A`, `B` and `C` are the same type
+    #[allow(clippy::type_complexity)]
+    pub fn get3_mut<A: 'static, B: 'static, C: 'static>(&mut self) -> (Option<&mut TypePool<A>>, Option<&mut TypePool<B>>, Option<&mut TypePool<C>>) {
+        let (a, b, c) = (TypeId::of::<A>(), TypeId::of::<B>(), TypeId::of::<C>());
+        assert!(a != b && a != c && b != c, "get3_mut requires three different types");
+
+        let a = self.type_pool_mut::<A>().map(|p| p as *mut TypePool<A>);
+        let b = self.type_pool_mut::<B>().map(|p| p as *mut TypePool<B>);
+        let c = self.type_pool_mut::<C>().map(|p| p as *mut TypePool<C>);
+        // safety: the types are different, so the pointers point to different pools
+        unsafe { (a.map(|p| &mut *p), b.map(|p| &mut *p), c.map(|p| &mut *p)) }
+    }
+
+    /// Get a reference to a type pool that is counted in
+    /// [`active_borrows`](Self::active_borrows) until it is dropped
+    #[cfg(feature = "debug-tracking")]
+    pub fn tracked_type_pool<T: 'static>(&self) -> Option<TrackedPool<'_, T>> {
+        let pool = self.type_pool::<T>()?;
+        self.borrows.set(self.borrows.get() + 1);
+        Some(TrackedPool { pool, borrows: &self.borrows })
+    }
+
+    /// Get a mutable reference to a type pool that is counted in
+    /// [`active_borrows`](Self::active_borrows) until it is dropped
+    #[cfg(feature = "debug-tracking")]
+    pub fn tracked_type_pool_mut<T: 'static>(&mut self) -> Option<TrackedPoolMut<'_, T>> {
+        self.empty_types = None;
+        let pool = self.pools.get_mut(&TypeId::of::<T>())
+            .and_then(|pool| TypePool::<T>::cast_mut(pool.as_mut()))?;
+        self.borrows.set(self.borrows.get() + 1);
+        Some(TrackedPoolMut { pool, borrows: &self.borrows })
+    }
+
+    /// The amount of tracked type pool references that are currently alive
+    #[cfg(feature = "debug-tracking")]
+    pub fn active_borrows(&self) -> usize {
+        self.borrows.get()
     }
 
     /// Add a value to the pools. If the type pool doesn't exst yet, it will be created
     pub fn push<T: 'static>(&mut self, value: T) {
-        let pools = self.pools.get_mut(&TypeId::of::<T>());
-        if let Some(pools) = pools {
-            unsafe { TypePool::<T>::cast_mut_unchecked(pools.as_mut()) }
-                .values.push(value);
+        if !self.observers.is_empty() {
+            self.notify_push(&value);
+        }
+
+        let exact = self.growth_policy == GrowthPolicy::Exact;
+        self.touch_clock += 1;
+        let touch = self.touch_clock;
+        let idx = match self.deque_mut::<T>() {
+            Some(deque) => {
+                if exact {
+                    deque.values.reserve_exact(1);
+                }
+                deque.values.push_back(value);
+                deque.last_touch = touch;
+                deque.values.len() - 1
+            }
+            None => {
+                let pool = self.pool_or_create::<T>();
+                if exact {
+                    pool.values.reserve_exact(1);
+                }
+                pool.values.push(value);
+                pool.touch(touch);
+                pool.values.len() - 1
+            }
+        };
+
+        self.empty_changed(idx == 0, false);
+        if self.events.is_some() {
+            self.record::<T>(PoolEventKind::Push, idx..idx + 1);
+        }
+    }
+
+    /// Add a value to the pools, returning a mutable reference to it
+    pub fn push_mut<T: 'static>(&mut self, value: T) -> &mut T {
+        self.push(value);
+        if self.type_deque::<T>().is_some() {
+            unsafe { self.deque_values_mut::<T>().unwrap_unchecked().back_mut().unwrap_unchecked() } // safety: the value was just pushed to the deque
         } else {
-            self.pools.insert(TypeId::of::<T>(), Box::new(TypePool::<T>::new()));
-            unsafe { TypePool::<T>::cast_mut_unchecked(self.pools.get_mut(&TypeId::of::<T>()).unwrap_unchecked().as_mut()) } // safety: I litterrally just created it
-                .values.push(value)
+            unsafe { self.pool_mut::<T>().unwrap_unchecked().values.last_mut().unwrap_unchecked() } // safety: the value was just pushed to the type pool
         }
     }
 
-    /// Returns the popped item or `None` if the value doesn't exist
-    pub fn pop<T: 'static>(&mut self) -> Option<T> {
-        self.type_pool_mut()
-            .and_then(|p| p.values.pop())
+    /// Add a value to the pools only if the type pool doesn't exist yet
+    ///
+    /// Returns whether the value was added
+    pub fn push_if_absent_type<T: 'static>(&mut self, value: T) -> bool {
+        if self.contains_type::<T>() {
+            return false;
+        }
+        self.push(value);
+        true
     }
 
-    /// Remove the value at the index in the type pool specified by `T`
-    pub fn remove<T: 'static>(&mut self, idx: usize) -> Option<T> {
-        self.type_pool_mut()
-            .and_then(|p| p.values.remove(idx))
+    /// Add a value to the pools, returning its typed index
+    pub fn push_indexed<T: 'static>(&mut self, value: T) -> PoolIndex<T> {
+        let idx = self.len_or_zero::<T>();
+        self.push(value);
+        PoolIndex(idx, PhantomData)
     }
 
-    /// Gets a value from a TypePool
-    ///
-    /// # Parameters
-    /// - idx: this is the index in the specific type `T` array
-    pub fn get<T: 'static>(&self, idx: usize) -> Option<&T> {
-        self.type_pool()
-            .and_then(|p| p.values.get(idx))
+    /// Add a default value to the pools, returning its index
+    pub fn push_default<T: 'static + Default>(&mut self) -> usize {
+        let idx = self.len_or_zero::<T>();
+        self.push(T::default());
+        idx
     }
 
-    /// Get a mutable reference to a value in a TypePool
-    pub fn get_mut<T: 'static>(&mut self, idx: usize) -> Option<&mut T> {
-        self.type_pool_mut()
-            .and_then(|p| p.values.get_mut(idx))
+    /// Add `n` default values to the type pool, returning the range of their indices
+    pub fn push_many_default<T: 'static + Default>(&mut self, n: usize) -> Range<usize> {
+        self.extend_indexed(std::iter::repeat_with(T::default).take(n))
     }
 
-    pub fn len<T: 'static>(&self) -> Option<usize> {
-        self.type_pool()
-            .map(|f: &TypePool<T>| f.values.len())
+    /// Add `a` and `b` to their type pools, returning the index of `a`. This keeps type pools
+    /// that are used as parallel arrays aligned
+    ///
+    /// In debug builds this panics if the type pools didn't have the same length before, in
+    /// which case the values end up at different indices.
+    pub fn push_pair<A: 'static, B: 'static>(&mut self, a: A, b: B) -> usize {
+        let idx = self.len_or_zero::<A>();
+        debug_assert_eq!(idx, self.len_or_zero::<B>(), "type pools have different lengths");
+        self.push(a);
+        self.push(b);
+        idx
     }
 
-    /// The amount of types stored in pools
-    pub fn types_count(&self) -> usize {
-        self.pools.keys().len()
+    /// Get the values of the type pool as raw bytes, without copying them
+    #[cfg(feature = "bytemuck")]
+    pub fn as_bytes<T: 'static + bytemuck::Pod>(&self) -> Option<&[u8]> {
+        self.type_pool::<T>()
+            .map(|p| bytemuck::cast_slice(&p.values))
     }
 
-    /// The types stored in pools
-    pub fn types(&self) -> Vec<&TypeId> {
-        self.pools.keys().collect()
+    /// Reinterpret `bytes` as values of `T` and add them to the end of the type pool. If the
+    /// type pool doesn't exist yet, it will be created
+    ///
+    /// `bytes` doesn't need to be aligned for `T`, but its length must be a multiple of the
+    /// size of `T`, otherwise an error is returned and nothing is added.
+    #[cfg(feature = "bytemuck")]
+    pub fn from_bytes<T: 'static + bytemuck::Pod>(&mut self, bytes: &[u8]) -> Result<(), bytemuck::PodCastError> {
+        let values = match bytemuck::try_cast_slice::<u8, T>(bytes) {
+            Ok(values) => values.to_vec(),
+            Err(bytemuck::PodCastError::TargetAlignmentGreaterAndInputNotAligned) => bytes
+                .chunks_exact(std::mem::size_of::<T>())
+                .map(bytemuck::pod_read_unaligned)
+                .collect(),
+            Err(err) => return Err(err),
+        };
+        self.push_all(values);
+        Ok(())
     }
 
-    /// Remove all entries for a type
-    pub fn remove_type<T: 'static>(&mut self) {
-        self.pools.remove(&TypeId::of::<T>());
+    /// Serialize the values of the type pool to JSON. Returns `Ok(None)` if the type pool
+    /// doesn't exist
+    #[cfg(feature = "serde")]
+    pub fn serialize_pool<T: 'static + serde::Serialize>(&self) -> Result<Option<Vec<u8>>, serde_json::Error> {
+        match self.type_deque::<T>() {
+            Some(deque) => serde_json::to_vec(deque).map(Some),
+            None => self.type_pool::<T>()
+                .map(|p| serde_json::to_vec(&p.values))
+                .transpose(),
+        }
     }
 
-    /// Remove all types which do not contain any values
-    pub fn remove_empty(&mut self) {
-        let to_remove = self.pools.keys()
-            // safety: the keys exist
-            .filter(|key| unsafe{ self.pools.get(key).unwrap_unchecked() }.is_empty())
-            .map(|key| *key)
-            .collect::<Vec<TypeId>>();
+    /// Replace the values of the type pool with the values deserialized from `data`, as
+    /// returned by [`serialize_pool`](Self::serialize_pool). If the type pool doesn't exist
+    /// yet, it will be created
+    ///
+    /// If `data` can't be deserialized the error is returned and the type pool is unchanged.
+    #[cfg(feature = "serde")]
+    pub fn deserialize_pool<T: 'static + serde::de::DeserializeOwned>(&mut self, data: &[u8]) -> Result<(), serde_json::Error> {
+        let values: Vec<T> = serde_json::from_slice(data)?;
+        self.set_values(values);
+        Ok(())
+    }
 
-        for id in to_remove {
-            self.pools.remove(&id);
+    /// Shuffle the values of the type pool in place
+    ///
+    /// Returns `None` if the type pool doesn't exist
+    #[cfg(feature = "rand")]
+    pub fn shuffle<T: 'static, R: rand::Rng + ?Sized>(&mut self, rng: &mut R) -> Option<()> {
+        use rand::seq::SliceRandom;
+        self.slice_mut::<T>()
+            .map(|values| values.shuffle(rng))
+    }
+
+    /// Get a random value of the type pool, or `None` if it is empty or doesn't exist
+    #[cfg(feature = "rand")]
+    pub fn choose<T: 'static, R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Option<&T> {
+        use rand::seq::SliceRandom;
+        match self.type_deque::<T>() {
+            Some(deque) if deque.is_empty() => None,
+            Some(deque) => deque.get(rng.gen_range(0..deque.len())),
+            None => self.type_pool::<T>()?.values.choose(rng),
         }
     }
 
-    /// Shrink the array containing all the pools to fit
-    pub fn shrink_to_fit(&mut self) {
-        self.pools.shrink_to_fit()
+    /// Get up to `n` distinct random values of the type pool, fewer if it has less than `n`
+    /// values
+    ///
+    /// Returns `None` if the type pool doesn't exist
+    #[cfg(feature = "rand")]
+    pub fn sample<T: 'static, R: rand::Rng + ?Sized>(&self, rng: &mut R, n: usize) -> Option<Vec<&T>> {
+        use rand::seq::SliceRandom;
+        match self.type_deque::<T>() {
+            Some(deque) => Some(rand::seq::index::sample(rng, deque.len(), n.min(deque.len()))
+                .into_iter()
+                .map(|idx| &deque[idx])
+                .collect()),
+            None => self.type_pool::<T>()
+                .map(|p| p.values.choose_multiple(rng, n).collect()),
+        }
     }
-}
 
-trait TypePoolTrait {
-    fn as_any(&self) -> &dyn Any;
-    fn as_any_mut(&mut self) -> &mut dyn Any;
-    fn is_empty(&self) -> bool;
-}
+    /// Add all values of `iter` to the type pool. If the type pool doesn't exist yet, it will
+    /// be created
+    pub fn extend<T: 'static, I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        if self.observers.contains_key(&TypeId::of::<T>()) || self.type_deque::<T>().is_some() {
+            iter.into_iter().for_each(|value| self.push(value));
+            return;
+        }
 
-pub struct TypePool<T> {
-    pub values: Vec<T>,
-}
+        self.touch_clock += 1;
+        let touch = self.touch_clock;
+        let exact = self.growth_policy == GrowthPolicy::Exact;
+        let pool = self.pool_or_create::<T>();
+        let start = pool.values.len();
+        let iter = iter.into_iter();
+        if exact {
+            pool.values.reserve_exact(iter.size_hint().0);
+        }
+        pool.values.extend(iter);
+        pool.touch(touch);
 
-impl<T: 'static> TypePool<T> {
-    fn new() -> Self {
-        Self { values: Vec::new() }
-    }
-
-    // fn cast(pool: &dyn TypePoolTrait) -> &Self {
-    //     pool.as_any()
-    //         .downcast_ref::<TypePool<T>>()
-    //         .unwrap()
-    // }
-    //
-    // fn cast_mut(pool: &mut dyn TypePoolTrait) -> &mut Self {
-    //     pool.as_any_mut()
-    //         .downcast_mut::<TypePool<T>>()
-    //         .unwrap()
-    // }
-
-    unsafe fn cast_unchecked(pool: &dyn TypePoolTrait) -> &Self {
-        pool.as_any()
-            .downcast_ref::<TypePool<T>>()
-            .unwrap_unchecked()
+        let end = pool.values.len();
+        self.empty_changed(start == 0, end == 0);
+        if self.events.is_some() {
+            self.record::<T>(PoolEventKind::Push, start..end);
+        }
     }
 
-    unsafe fn cast_mut_unchecked(pool: &mut dyn TypePoolTrait) -> &mut Self {
-        pool.as_any_mut()
-            .downcast_mut::<TypePool<T>>()
-            .unwrap_unchecked()
+    /// Add all values of `iter` to the type pool, returning the range of their indices. If the
+    /// type pool doesn't exist yet, it will be created
+    pub fn extend_indexed<T: 'static, I: IntoIterator<Item = T>>(&mut self, iter: I) -> Range<usize> {
+        let start = self.len_or_zero::<T>();
+        self.extend(iter);
+        start..self.len_or_zero::<T>()
     }
-}
 
-impl<T: 'static> TypePoolTrait for TypePool<T> {
-    fn as_any(&self) -> &dyn Any {
-        self
+    /// Move all `values` to the end of the type pool without cloning them. If the type pool
+    /// doesn't exist yet, it will be created
+    pub fn push_all<T: 'static>(&mut self, mut values: Vec<T>) {
+        if self.type_deque::<T>().is_some() {
+            self.extend(values);
+            return;
+        }
+        if !self.observers.is_empty() {
+            values.iter().for_each(|value| self.notify_push(value));
+        }
+        self.touch_clock += 1;
+        let touch = self.touch_clock;
+        let pool = self.pool_or_create::<T>();
+        let start = pool.values.len();
+        pool.values.append(&mut values);
+        pool.touch(touch);
+
+        let end = pool.values.len();
+        self.empty_changed(start == 0, end == 0);
+        if self.events.is_some() {
+            self.record::<T>(PoolEventKind::Push, start..end);
+        }
     }
 
-    fn as_any_mut(&mut self) -> &mut dyn Any {
-        self
+    /// Copy all values of `slice` to the end of the type pool, reserving capacity for them at
+    /// once. If the type pool doesn't exist yet, it will be created
+    pub fn push_slice<T: 'static + Copy>(&mut self, slice: &[T]) {
+        if self.type_deque::<T>().is_some() {
+            self.extend(slice.iter().copied());
+            return;
+        }
+        if !self.observers.is_empty() {
+            slice.iter().for_each(|value| self.notify_push(value));
+        }
+        self.touch_clock += 1;
+        let touch = self.touch_clock;
+        let pool = self.pool_or_create::<T>();
+        let start = pool.values.len();
+        pool.values.reserve(slice.len());
+        pool.values.extend_from_slice(slice);
+        pool.touch(touch);
+
+        let end = pool.values.len();
+        self.empty_changed(start == 0, end == 0);
+        if self.events.is_some() {
+            self.record::<T>(PoolEventKind::Push, start..end);
+        }
     }
 
-    fn is_empty(&self) -> bool {
-        self.values.is_empty()
+    /// Add all values of `iter` to the type pool, reserving exactly enough capacity for them
+    /// first. If the type pool doesn't exist yet, it will be created
+    pub fn extend_exact<T: 'static, I: IntoIterator<Item = T>>(&mut self, iter: I)
+    where
+        I::IntoIter: ExactSizeIterator,
+    {
+        let iter = iter.into_iter();
+        match self.deque_values_mut::<T>() {
+            Some(deque) => deque.reserve_exact(iter.len()),
+            None => self.pool_or_create::<T>().values.reserve_exact(iter.len()),
+        }
+        self.extend(iter);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::TypePools;
+    /// Returns the popped item or `None` if the value doesn't exist
+    pub fn pop<T: 'static>(&mut self) -> Option<T> {
+        if self.type_deque::<T>().is_some() {
+            return self.pop_back();
+        }
+        let (value, idx) = self.pool_mut::<T>()
+            .and_then(|p| Some((p.values.pop()?, p.values.len())))?;
+        self.removed(PoolEventKind::Remove, &value, idx, idx == 0);
+        Some(value)
+    }
 
-    #[test]
-    fn test_add() {
-        let mut pools = TypePools::new();
-        pools.push(1 as u32);
-        pools.push(2 as u32);
-        pools.push("Hello");
-        pools.push("World");
+    /// Removes and returns up to `n` values from the end of the type pool, in the order they
+    /// would be popped: the last value comes first
+    pub fn pop_n<T: 'static>(&mut self, n: usize) -> Vec<T> {
+        let Some((start, popped, emptied)) = self.with_values::<T, _>(|values| {
+            let start = values.len().saturating_sub(n);
+            let popped: Vec<T> = values.drain(start..).rev().collect();
+            (start, popped, start == 0)
+        }) else { return Vec::new() };
+        self.removed_all(PoolEventKind::Remove, &popped, (start..start + popped.len()).rev(), emptied);
+        popped
+    }
 
-        assert_eq!(*pools.get::<u32>(0).unwrap(), 1);
-        assert_eq!(*pools.get::<u32>(1).unwrap(), 2);
-        assert_eq!(*pools.get::<&str>(0).unwrap(), "Hello");
-        assert_eq!(*pools.get::<&str>(1).unwrap(), "World");
+    /// Removes and returns the first value of the type pool, or `None` if it is empty or
+    /// doesn't exist
+    ///
+    /// This shifts all remaining values, so it is O(n), unless the type is in deque mode
+    pub fn pop_front<T: 'static>(&mut self) -> Option<T> {
+        match self.deque_values_mut::<T>() {
+            Some(deque) => {
+                let value = deque.pop_front()?;
+                let emptied = deque.is_empty();
+                self.removed(PoolEventKind::Remove, &value, 0, emptied);
+                Some(value)
+            }
+            None => self.remove(0),
+        }
     }
 
-    #[test]
-    fn test_example() {
-        // Create a Type Pools structure
-        let mut pools = TypePools::new();
+    /// Removes and returns the last value of the type pool, also for types in deque mode
+    pub fn pop_back<T: 'static>(&mut self) -> Option<T> {
+        match self.deque_values_mut::<T>() {
+            Some(deque) => {
+                let value = deque.pop_back()?;
+                let idx = deque.len();
+                self.removed(PoolEventKind::Remove, &value, idx, idx == 0);
+                Some(value)
+            }
+            None => self.pop(),
+        }
+    }
 
-        // Adding values
-        pools.push(1 as u32);
-        pools.push(2 as u32);
-        pools.push("Hello world");
+    /// Add a value to the end of the type pool, also for types in deque mode
+    pub fn push_back<T: 'static>(&mut self, value: T) {
+        self.push(value);
+    }
 
-        // Query values
-        let int_pool = pools.type_pool::<u32>().unwrap();
-        let int_value: u32 = int_pool.values[0];
-        let string_value: &str = pools.get::<&str>(0).unwrap();
+    /// Add a value to the start of the type pool
+    ///
+    /// This shifts all values, so it is O(n), unless the type is in deque mode
+    pub fn push_front<T: 'static>(&mut self, value: T) {
+        if !self.observers.is_empty() {
+            self.notify_push(&value);
+        }
+        self.touch_clock += 1;
+        let touch = self.touch_clock;
+        let len = match self.deque_mut::<T>() {
+            Some(deque) => {
+                deque.values.push_front(value);
+                deque.last_touch = touch;
+                deque.values.len()
+            }
+            None => {
+                let pool = self.pool_or_create::<T>();
+                pool.values.insert(0, value);
+                pool.touch(touch);
+                pool.values.len()
+            }
+        };
 
-        assert_eq!(int_value, 1);
-        assert_eq!(string_value, "Hello world");
+        self.empty_changed(len == 1, false);
+        if self.events.is_some() {
+            self.record::<T>(PoolEventKind::Push, 0..1);
+        }
+    }
+
+    /// Store the values of `T` in a `VecDeque` instead of a type pool, making
+    /// [`push_front`](Self::push_front) and [`pop_front`](Self::pop_front) O(1)
+    ///
+    /// Values already in the type pool are moved to the deque, which takes the place of the type
+    /// pool: it counts towards [`types_count`](Self::types_count), [`len`](Self::len) and the
+    /// other type erased operations. The values can be accessed through
+    /// [`type_deque`](Self::type_deque), [`type_deque_mut`](Self::type_deque_mut) and the
+    /// methods of `TypePools` that take or return values. Methods returning a `TypePool<T>`, a
+    /// `Vec` iterator or a slice borrowed from `&self` return `None` for types in deque mode, or
+    /// end deque mode if they would create the type pool, see
+    /// [`get_or_create_pool`](Self::get_or_create_pool). Methods that need the values in a `Vec`
+    /// or a mutable slice, like the retain and drain methods, move them out of the deque for the
+    /// call, which is O(n).
+    pub fn make_deque<T: 'static>(&mut self) {
+        if self.type_deque::<T>().is_some() {
+            return;
+        }
+        let values = match self.pools.remove(&TypeId::of::<T>()) {
+            Some(mut pool) => TypePool::<T>::cast_mut(pool.as_mut())
+                .map(|pool| std::mem::take(&mut pool.values))
+                .unwrap_or_default(),
+            None => {
+                self.evict_for_new_type();
+                self.empty_changed(false, true);
+                Vec::new()
+            }
+        };
+        self.touch_clock += 1;
+        let deque = TypeDeque { values: VecDeque::from(values), last_touch: self.touch_clock };
+        self.pools.insert(TypeId::of::<T>(), Box::new(deque));
+    }
+
+    /// Get a reference to the deque of a type in deque mode
+    pub fn type_deque<T: 'static>(&self) -> Option<&VecDeque<T>> {
+        self.pools.get(&TypeId::of::<T>())
+            .and_then(|pool| TypeDeque::<T>::cast(pool.as_ref()))
+            .map(|deque| &deque.values)
+    }
+
+    /// Get a mutable reference to the deque of a type in deque mode
+    pub fn type_deque_mut<T: 'static>(&mut self) -> Option<&mut VecDeque<T>> {
+        self.empty_types = None;
+        self.deque_values_mut()
+    }
+
+    /// Like [`type_deque_mut`](Self::type_deque_mut), for callers that keep the count of empty
+    /// type pools up to date themselves
+    fn deque_values_mut<T: 'static>(&mut self) -> Option<&mut VecDeque<T>> {
+        self.deque_mut::<T>()
+            .map(|deque| &mut deque.values)
+    }
+
+    fn deque_mut<T: 'static>(&mut self) -> Option<&mut TypeDeque<T>> {
+        self.pools.get_mut(&TypeId::of::<T>())
+            .and_then(|pool| TypeDeque::<T>::cast_mut(pool.as_mut()))
+    }
+
+    /// Remove the value at the index in the type pool specified by `T`
+    ///
+    /// Returns `None` if the type pool doesn't exist or `idx` is out of bounds
+    pub fn remove<T: 'static>(&mut self, idx: usize) -> Option<T> {
+        let (value, emptied) = match self.deque_values_mut::<T>() {
+            Some(deque) => (deque.remove(idx)?, deque.is_empty()),
+            None => self.pool_mut::<T>()
+                .filter(|p| idx < p.values.len())
+                .map(|p| (p.values.remove(idx), p.values.is_empty()))?,
+        };
+        self.removed(PoolEventKind::Remove, &value, idx, emptied);
+        Some(value)
+    }
+
+    /// Start a batch of pushes to the type pool of `T`, reserving space for `expected` values.
+    /// If the type pool doesn't exist yet, it will be created
+    ///
+    /// The batch holds on to the type pool, so pushing through it avoids a lookup per value.
+    /// Observers registered with [`on_push`](Self::on_push) are not called for values pushed
+    /// through a batch.
+    pub fn batch<T: 'static>(&mut self, expected: usize) -> Batch<'_, T> {
+        self.touch_clock += 1;
+        let touch = self.touch_clock;
+        if !self.contains_type::<T>() {
+            self.pool_or_create::<T>();
+        }
+        let pool = unsafe { self.pools.get_mut(&TypeId::of::<T>()).unwrap_unchecked() }; // safety: the pool was created above if it didn't exist
+        let target = if pool.as_any().is::<TypeDeque<T>>() {
+            let deque = unsafe { TypeDeque::<T>::cast_mut(pool.as_mut()).unwrap_unchecked() }; // safety: checked above
+            deque.values.reserve(expected);
+            deque.last_touch = touch;
+            BatchTarget::Deque(&mut deque.values)
+        } else {
+            let pool = unsafe { TypePool::<T>::cast_mut(pool.as_mut()).unwrap_unchecked() }; // safety: a type is stored in a type pool if it isn't in deque mode
+            pool.values.reserve(expected);
+            pool.touch(touch);
+            BatchTarget::Pool(pool)
+        };
+        Batch { target, events: self.events.as_mut(), empty_types: &mut self.empty_types }
+    }
+
+    /// Retain only the values for which `pred` returns `true`. The predicate receives the
+    /// original index of each value, even after earlier values were removed
+    pub fn retain_with_index<T: 'static, F: FnMut(usize, &T) -> bool>(&mut self, mut pred: F) {
+        self.retain_tracked::<T, _>(|idx, value| pred(idx, value));
+    }
+
+    /// Remove all repeated values of the type pool, keeping the first occurrence of every value
+    /// in order, unlike [`TypePool::dedup`] which only removes consecutive repeated values
+    pub fn dedup_unordered<T: 'static + Eq + Hash>(&mut self) {
+        let Some(values) = self.iter_values::<T>() else { return };
+        let mut seen = HashSet::with_capacity(values.len());
+        let keep: Vec<bool> = values
+            .map(|value| seen.insert(value))
+            .collect();
+        self.retain_tracked::<T, _>(|idx, _| keep[idx]);
+    }
+
+    /// Update every value of the type pool with `f`, keeping only the values for which it
+    /// returns [`Keep::Yes`]
+    pub fn retain_update<T: 'static, F: FnMut(&mut T) -> Keep>(&mut self, mut f: F) {
+        self.retain_tracked::<T, _>(|_, value| f(value) == Keep::Yes);
+    }
+
+    /// Retain only the values for which `pred` returns `true`, returning how many values were
+    /// removed
+    pub fn retain_count<T: 'static, F: FnMut(&T) -> bool>(&mut self, mut pred: F) -> usize {
+        self.retain_tracked::<T, _>(|_, value| pred(value))
+            .unwrap_or(0)
+    }
+
+    /// Retain only the values for which `pred` returns `true`, returning the removed values in
+    /// their original order
+    pub fn retain_collecting<T: 'static, F: FnMut(&T) -> bool>(&mut self, mut pred: F) -> Vec<T> {
+        self.extract_where::<T, _>(|value| !pred(value))
+    }
+
+    /// Move the values for which `pred` returns `true` to the end of the type pool in `dst`,
+    /// returning how many values were moved. If the type pool doesn't exist yet in `dst`, it
+    /// will be created
+    pub fn drain_where_into<T: 'static, F: FnMut(&T) -> bool>(&mut self, pred: F, dst: &mut TypePools) -> usize {
+        let moved = self.extract_where::<T, _>(pred);
+        let count = moved.len();
+        if count > 0 {
+            dst.push_all(moved);
+        }
+        count
+    }
+
+    /// Remove the first value equal to `value` by swapping it with the last value.
+    /// This does not preserve the order of the type pool, but is O(1) after finding the value
+    pub fn swap_remove_value<T: 'static + PartialEq>(&mut self, value: &T) -> Option<T> {
+        let idx = self.first_index_of(value)?;
+        self.swap_remove_tracked::<T>(idx)
+            .map(|(removed, _)| removed)
+    }
+
+    /// Remove the first value for which `pred` returns `true` by swapping the last value into
+    /// its place. This does not preserve the order of the type pool
+    ///
+    /// Returns `None` if the type pool doesn't exist or no value matches
+    pub fn swap_remove_where<T: 'static, F: FnMut(&T) -> bool>(&mut self, pred: F) -> Option<T> {
+        let idx = self.iter_values::<T>()?.position(pred)?;
+        self.swap_remove_tracked::<T>(idx)
+            .map(|(removed, _)| removed)
+    }
+
+    /// Remove the value at `idx` by swapping the last value into its place. Also returns the
+    /// old index of the value that was moved to `idx`, or `None` if no value was moved because
+    /// `idx` was the last index
+    ///
+    /// Returns `None` if the type pool doesn't exist or `idx` is out of bounds
+    pub fn swap_remove_tracked<T: 'static>(&mut self, idx: usize) -> Option<(T, Option<usize>)> {
+        let last = self.len::<T>()?.checked_sub(1)?;
+        let removed = match self.deque_values_mut::<T>() {
+            Some(deque) => deque.swap_remove_back(idx)?,
+            None => self.pool_mut::<T>()
+                .filter(|_| idx <= last)
+                .map(|p| p.values.swap_remove(idx))?,
+        };
+        let (moved, emptied) = ((idx != last).then_some(last), last == 0);
+        self.removed(PoolEventKind::SwapRemove, &removed, idx, emptied);
+        Some((removed, moved))
+    }
+
+    /// Move the value at `idx` of the type pool to the end of the type pool in `dst`.
+    /// If the type pool doesn't exist yet in `dst`, it will be created
+    ///
+    /// Returns `None` if the value doesn't exist in `self`
+    pub fn transfer<T: 'static>(&mut self, idx: usize, dst: &mut TypePools) -> Option<()> {
+        let value = self.remove::<T>(idx)?;
+        dst.push(value);
+        Some(())
+    }
+
+    /// Convert every value of the type pool of `Old` with `f` and move them to the end of the
+    /// type pool of `New`, removing the type pool of `Old`
+    ///
+    /// Stops at the first error and returns it, leaving partial state behind: the values
+    /// converted so far are moved to the type pool of `New`, the value that failed is dropped
+    /// and the values after it stay in the type pool of `Old`.
+    pub fn migrate<Old: 'static, New: 'static, F: FnMut(Old) -> Result<New, E>, E>(&mut self, mut f: F) -> Result<(), E> {
+        let Some(old) = self.with_values::<Old, _>(std::mem::take) else { return Ok(()) };
+        let mut old = old.into_iter();
+        let mut converted = Vec::with_capacity(old.len());
+        let result = old.by_ref()
+            .try_for_each(|value| f(value).map(|value| converted.push(value)));
+
+        match &result {
+            Ok(()) => self.remove_type::<Old>(),
+            Err(_) => { self.with_values::<Old, _>(|values| values.extend(old)); }
+        }
+        self.push_all(converted);
+        result
+    }
+
+    /// Convert every value of the type pool of `Old` to `New` and move them to the end of the
+    /// type pool of `New`, removing the type pool of `Old`
+    ///
+    /// Returns `None` if the type pool of `Old` doesn't exist
+    pub fn reinterpret<Old: 'static, New: 'static + From<Old>>(&mut self) -> Option<()> {
+        if !self.contains_type::<Old>() {
+            return None;
+        }
+        let Ok(()) = self.migrate::<Old, New, _, std::convert::Infallible>(|value| Ok(New::from(value)));
+        Some(())
+    }
+
+    /// Move the whole type pool to `dst` without copying its values, replacing the type pool of
+    /// `dst` if it has one
+    ///
+    /// Returns whether a type pool was moved
+    pub fn move_pool<T: 'static>(&mut self, dst: &mut TypePools) -> bool {
+        let Some(mut pool) = self.pools.remove(&TypeId::of::<T>()) else { return false };
+        pool.update_peak();
+        dst.touch_clock = dst.touch_clock.max(self.touch_clock);
+        let is_empty = pool.is_empty();
+        self.empty_changed(is_empty, false);
+        if !dst.contains_type::<T>() {
+            dst.evict_for_new_type();
+        }
+        if let Some(old) = dst.pools.insert(TypeId::of::<T>(), pool) {
+            dst.empty_changed(old.is_empty(), false);
+        }
+        dst.empty_changed(false, is_empty);
+        true
+    }
+
+    /// Lazily remove all values from the type pool
+    ///
+    /// All values are removed when the iterator is dropped, even the ones that weren't
+    /// consumed. Observers registered with [`on_remove`](Self::on_remove) are not called.
+    ///
+    /// Returns `None` if the type pool doesn't exist or `T` is in deque mode.
+    pub fn drain_iter<T: 'static>(&mut self) -> Option<std::vec::Drain<'_, T>> {
+        if self.type_pool::<T>().is_some_and(|p| !p.values.is_empty()) {
+            self.empty_changed(false, true);
+            if self.events.is_some() {
+                self.record::<T>(PoolEventKind::Clear, 0..1);
+            }
+        }
+        self.pool_mut::<T>()
+            .map(|p| p.values.drain(..))
+    }
+
+    /// Remove the values in `range` from the type pool, returning them in reverse order
+    ///
+    /// # Panics
+    /// If the range is out of bounds, like [`Vec::drain`]
+    pub fn drain_rev<T: 'static, R: RangeBounds<usize>>(&mut self, range: R) -> Option<Vec<T>> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let (drained, emptied) = self.with_values::<T, _>(|values| {
+            let drained: Vec<T> = values.drain(range).rev().collect();
+            (drained, values.is_empty())
+        })?;
+        self.removed_all(PoolEventKind::Remove, &drained, (start..start + drained.len()).rev(), emptied);
+        Some(drained)
+    }
+
+    /// Remove all values from the type pool and return them sorted
+    pub fn drain_sorted<T: 'static + Ord>(&mut self) -> Option<Vec<T>> {
+        let mut values = self.with_values::<T, _>(std::mem::take)?;
+        values.sort();
+        self.removed_all(PoolEventKind::Clear, &values, (!values.is_empty()).then_some(0).into_iter(), true);
+        Some(values)
+    }
+
+    /// Remove the first `n` values of the type pool, or all values if it has fewer
+    ///
+    /// This shifts all remaining values, so it is O(n)
+    pub fn trim_front<T: 'static>(&mut self, n: usize) {
+        let Some((trimmed, emptied)) = self.with_values::<T, _>(|values| {
+            let trimmed: Vec<T> = values.drain(..n.min(values.len())).collect();
+            (trimmed, values.is_empty())
+        }) else { return };
+        self.removed_all(PoolEventKind::Remove, &trimmed, (0..trimmed.len()).rev(), emptied);
+    }
+
+    /// Remove the last `n` values of the type pool, or all values if it has fewer
+    pub fn trim_back<T: 'static>(&mut self, n: usize) {
+        let Some((len, trimmed)) = self.with_values::<T, _>(|values| {
+            let trimmed = values.split_off(values.len().saturating_sub(n));
+            (values.len(), trimmed)
+        }) else { return };
+        self.removed_all(PoolEventKind::Remove, &trimmed, (len..len + trimmed.len()).rev(), len == 0);
+    }
+
+    /// Remove the values at all `indices` by swapping them with the last value. Duplicate and
+    /// out of bounds indices are ignored
+    ///
+    /// The removed values are returned in descending order of their index. This does not
+    /// preserve the order of the type pool.
+    pub fn swap_remove_many<T: 'static>(&mut self, indices: &[usize]) -> Vec<T> {
+        let Some(len) = self.len::<T>() else { return Vec::new() };
+        let mut indices = indices.to_vec();
+        indices.sort_unstable_by(|a, b| b.cmp(a));
+        indices.dedup();
+        indices.retain(|&idx| idx < len);
+        let removed: Vec<T> = unsafe { self.with_values::<T, _>(|values| indices.iter().map(|&idx| values.swap_remove(idx)).collect()).unwrap_unchecked() }; // safety: the type pool exists
+        let emptied = removed.len() == len;
+        self.removed_all(PoolEventKind::SwapRemove, &removed, indices.into_iter(), emptied);
+        removed
+    }
+
+    /// Register a callback that is called with every value pushed to the type pool of `T`
+    pub fn on_push<T: 'static, F: FnMut(&T) + 'static>(&mut self, f: F) {
+        self.observers_mut::<T>().on_push.push(Box::new(f));
+    }
+
+    /// Register a callback that is called with every value popped or removed from the
+    /// type pool of `T`
+    pub fn on_remove<T: 'static, F: FnMut(&T) + 'static>(&mut self, f: F) {
+        self.observers_mut::<T>().on_remove.push(Box::new(f));
+    }
+
+    /// Register a callback that is called when the type pool of `T` becomes empty by `pop`,
+    /// `pop_n`, `pop_front`, `pop_back`, `remove`, `truncate_returning`, `drain_rev`,
+    /// `drain_sorted`, `set_values`, a swap remove, a retain or a trim
+    pub fn on_empty<T: 'static, F: FnMut() + 'static>(&mut self, f: F) {
+        self.observers_mut::<T>().on_empty.push(Box::new(f));
+    }
+
+    /// Move all values into `sink`, appending them to its type pools. Type pools that don't
+    /// exist in `sink` yet will be created.
+    ///
+    /// The type pools of `self` remain, empty, and keep their allocated capacity.
+    pub fn drain_into(&mut self, sink: &mut TypePools) {
+        for pool in self.pools.values_mut() {
+            pool.drain_into(sink);
+        }
+        self.empty_types = Some(self.pools.len());
+    }
+
+    /// Reserve capacity for at least `additional` more values in every type pool
+    pub fn reserve_all(&mut self, additional: usize) {
+        for pool in self.pools.values_mut() {
+            pool.reserve(additional);
+        }
+    }
+
+    /// Reserve capacity in every type pool for as many more values as the matching type pool of
+    /// `other` holds, e.g. before merging `other` into `self`
+    ///
+    /// Only existing type pools are reserved, types that are missing in `self` can be created
+    /// first with [`ensure_pool`](Self::ensure_pool).
+    pub fn reserve_like(&mut self, other: &TypePools) {
+        for (id, pool) in self.pools.iter_mut() {
+            if let Some(other) = other.pools.get(id) {
+                pool.reserve(other.len());
+            }
+        }
+    }
+
+    /// Create new pools containing only a type pool of `U`, made by mapping every value of
+    /// the type pool of `T` with `f`. If the type pool of `T` doesn't exist, the new pools
+    /// are empty
+    pub fn project<T: 'static, U: 'static, F: FnMut(&T) -> U>(&self, f: F) -> TypePools {
+        match self.iter_values::<T>() {
+            Some(values) => TypePools::from_pool(values.map(f).collect()),
+            None => TypePools::new(),
+        }
+    }
+
+    /// Returns `true` if the type pools of `self` and `other` contain the same values, ignoring
+    /// their order. A missing type pool is treated as empty
+    pub fn eq_unordered<T: 'static + Ord + Clone>(&self, other: &TypePools) -> bool {
+        let sorted = |pools: &TypePools| {
+            let mut values = pools.collect::<T>();
+            values.sort();
+            values
+        };
+        sorted(self) == sorted(other)
+    }
+
+    /// The indices at which the type pools of `self` and `other` differ. If one type pool is
+    /// longer, its extra indices are included. A missing type pool is treated as empty
+    ///
+    /// Returns `None` if neither `self` nor `other` has the type pool
+    pub fn diff_pool<T: 'static + PartialEq>(&self, other: &TypePools) -> Option<Vec<usize>> {
+        if !self.contains_type::<T>() && !other.contains_type::<T>() {
+            return None;
+        }
+        let (a, b) = (self.len_or_zero::<T>(), other.len_or_zero::<T>());
+        let mut diff: Vec<usize> = self.iter_values::<T>().into_iter().flatten()
+            .zip(other.iter_values::<T>().into_iter().flatten())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(idx, _)| idx)
+            .collect();
+        diff.extend(a.min(b)..a.max(b));
+        Some(diff)
+    }
+
+    /// Clone the values of the types registered in `registry` and append them to `dst`
+    ///
+    /// Returns the types that were skipped because they aren't registered
+    pub fn clone_registered_into(&self, registry: &CloneRegistry, dst: &mut TypePools) -> Vec<TypeId> {
+        let mut skipped = Vec::new();
+        for id in self.pools.keys() {
+            match registry.cloners.get(id) {
+                Some(clone_into) => clone_into(self, dst),
+                None => skipped.push(*id),
+            }
+        }
+        skipped
+    }
+
+    /// Remove the values for which the predicates registered in `registry` return `true`,
+    /// returning the removed values per type. Types that aren't registered are left untouched
+    pub fn sweep_registered(&mut self, registry: &mut SweepRegistry) -> Swept {
+        self.empty_types = None;
+        let mut removed = HashMap::new();
+        for (id, sweep) in registry.sweepers.iter_mut() {
+            if let Some(pool) = self.pools.get_mut(id) {
+                removed.insert(*id, sweep(pool.as_mut()));
+            }
+        }
+        Swept { removed }
+    }
+
+    /// Call the visitor registered in `registry` for every type pool with the mutable slice of
+    /// its values. Type pools without a registered visitor are skipped
+    pub fn visit_all_mut(&mut self, registry: &mut VisitorRegistry) {
+        for (id, pool) in self.pools.iter_mut() {
+            if let Some(visit) = registry.visitors.get_mut(id) {
+                visit(pool.as_mut());
+            }
+        }
+    }
+
+    /// Create an empty type pool for the type with `id` if it doesn't exist yet, using the
+    /// constructor registered in `registry`
+    ///
+    /// Returns `false` if the type pool doesn't exist and the type isn't registered
+    pub fn ensure_pool(&mut self, id: TypeId, registry: &ConstructorRegistry) -> bool {
+        if self.pools.contains_key(&id) {
+            return true;
+        }
+        let Some(construct) = registry.constructors.get(&id) else { return false };
+        self.evict_for_new_type();
+        let pool = construct();
+        self.empty_changed(false, pool.is_empty());
+        self.pools.insert(id, pool);
+        true
+    }
+
+    /// Limit the amount of type pools to `n`.
+    ///
+    /// When a push would create a new type pool while there are already `n` type pools,
+    /// one pool is evicted first: empty pools are evicted before non-empty pools, and
+    /// among those the pool that was pushed to least recently is evicted. If there are more
+    /// than `n` type pools when setting the limit, pools are evicted the same way until `n`
+    /// are left. A limit of 0 still keeps the type pool that is pushed to.
+    pub fn set_max_types(&mut self, n: usize) {
+        self.max_types = Some(n);
+        self.evict_to(n);
+    }
+
+    /// Choose how type pools grow when values are added with `push` or `extend`
+    pub fn set_growth_policy(&mut self, policy: GrowthPolicy) {
+        self.growth_policy = policy;
+    }
+
+    /// Allocate capacity for `cap` values when the type pool of `T` is created. Does nothing
+    /// if the type pool already exists
+    pub fn hint_capacity<T: 'static>(&mut self, cap: usize) {
+        if !self.contains_type::<T>() {
+            self.capacity_hints.insert(TypeId::of::<T>(), cap);
+        }
+    }
+
+    /// Automatically call [`remove_empty`](Self::remove_empty) when removing values, like with
+    /// `pop`, `remove` or `retain_count`, empties a type pool while at least `threshold` type
+    /// pools are empty
+    pub fn set_auto_prune(&mut self, threshold: usize) {
+        self.auto_prune = Some(threshold);
+    }
+
+    /// Check the internal consistency of the pools, useful in tests and `debug_assert`s
+    ///
+    /// # Panics
+    /// Panics with a description of the first violation found:
+    /// - a type pool or deque is stored under the id of a different type. Deques are stored in
+    ///   the same map as type pools, so a type can't have both
+    /// - there are more type pools than allowed by [`set_max_types`](Self::set_max_types)
+    /// - a type pool was touched later than the current touch clock
+    /// - a type pool is longer than its recorded peak, while no type pool was accessed directly
+    ///   since the type pools were last scanned
+    /// - the count of empty type pools is out of date
+    /// - a recorded [`PoolEventKind::Clear`] has an index other than 0
+    pub fn assert_invariants(&self) {
+        if let Some(max_types) = self.max_types {
+            assert!(self.pools.len() <= max_types.max(1), "{} type pools exceed the limit of {}", self.pools.len(), max_types);
+        }
+        for (id, pool) in self.pools.iter() {
+            assert_eq!(*id, pool.value_type_id(), "type pool of {} is stored under the wrong type id", pool.type_name());
+            assert!(pool.last_touch() <= self.touch_clock, "type pool of {} was touched in the future", pool.type_name());
+            if let (Some(peak), Some(_)) = (pool.peak_len(), self.empty_types) {
+                assert!(pool.len() <= peak, "type pool of {} holds {} values, more than its peak of {}", pool.type_name(), pool.len(), peak);
+            }
+        }
+        if let Some(count) = self.empty_types {
+            let empty = self.pools.values().filter(|pool| pool.is_empty()).count();
+            assert_eq!(count, empty, "the count of empty type pools is out of date");
+        }
+        for event in self.events.iter().flatten() {
+            if event.kind == PoolEventKind::Clear {
+                assert_eq!(event.index, 0, "clear event recorded with an index");
+            }
+        }
+    }
+
+    /// Bookkeeping after `value` was removed from index `idx` of its type pool, recorded as
+    /// `kind`. `emptied` is `true` if the type pool is empty now
+    fn removed<T: 'static>(&mut self, kind: PoolEventKind, value: &T, idx: usize, emptied: bool) {
+        if !self.observers.is_empty() {
+            self.notify_remove(value);
+        }
+        if self.events.is_some() {
+            self.record::<T>(kind, idx..idx + 1);
+        }
+        if emptied {
+            self.empty_changed(false, true);
+            self.notify_empty::<T>();
+            self.pool_emptied();
+        }
+    }
+
+    /// Bookkeeping after `values` were removed from `indices` of their type pool, like
+    /// [`removed`](Self::removed) for each value. `emptied` is `true` if the type pool is empty
+    /// now. The values must have been removed through [`with_values`](Self::with_values), which
+    /// already counted the emptied type pool
+    fn removed_all<T: 'static>(&mut self, kind: PoolEventKind, values: &[T], indices: impl Iterator<Item = usize>, emptied: bool) {
+        if !self.observers.is_empty() {
+            values.iter().for_each(|value| self.notify_remove(value));
+        }
+        if self.events.is_some() {
+            self.record::<T>(kind, indices);
+        }
+        if emptied && !values.is_empty() {
+            self.notify_empty::<T>();
+            self.pool_emptied();
+        }
+    }
+
+    /// Retain the values for which `keep` returns `true`, passing it the original index of each
+    /// value. Returns the amount of removed values, or `None` if the type pool doesn't exist
+    fn retain_tracked<T: 'static, F: FnMut(usize, &mut T) -> bool>(&mut self, mut keep: F) -> Option<usize> {
+        let recording = self.events.is_some();
+        let mut indices = Vec::new();
+        // the observers are taken out during the retain, so they can be called with the removed
+        // values while the type pool is borrowed
+        let mut observers = self.observers.remove(&TypeId::of::<T>());
+        let mut on_remove = observers.as_mut()
+            .map(|observers| unsafe { &mut observers.downcast_mut::<Observers<T>>().unwrap_unchecked().on_remove }); // safety: observers are stored under the id of their type
+        let retained = self.with_values::<T, _>(|values| {
+            let len = values.len();
+            let mut idx = 0;
+            values.retain_mut(|value| {
+                let kept = keep(idx, value);
+                if !kept {
+                    if recording {
+                        indices.push(idx);
+                    }
+                    if let Some(on_remove) = &mut on_remove {
+                        on_remove.iter_mut().for_each(|f| f(value));
+                    }
+                }
+                idx += 1;
+                kept
+            });
+            (len, values.len())
+        });
+        if let Some(observers) = observers {
+            self.observers.insert(TypeId::of::<T>(), observers);
+        }
+        let (len, remaining) = retained?;
+        let removed = len - remaining;
+
+        if recording {
+            self.record::<T>(PoolEventKind::Remove, indices.into_iter().rev());
+        }
+        if len > 0 && removed == len {
+            self.notify_empty::<T>();
+            self.pool_emptied();
+        }
+        Some(removed)
+    }
+
+    /// Remove the values for which `pred` returns `true` from the type pool, returning them in
+    /// their original order. The removals are notified and recorded like the ones of `remove`
+    fn extract_where<T: 'static, F: FnMut(&T) -> bool>(&mut self, mut pred: F) -> Vec<T> {
+        let recording = self.events.is_some();
+        let mut extracted = Vec::new();
+        let mut indices = Vec::new();
+        let Some(emptied) = self.with_values::<T, _>(|kept| {
+            let values = std::mem::take(kept);
+            kept.reserve(values.len());
+            for (idx, value) in values.into_iter().enumerate() {
+                if pred(&value) {
+                    if recording {
+                        indices.push(idx);
+                    }
+                    extracted.push(value);
+                } else {
+                    kept.push(value);
+                }
+            }
+            kept.is_empty()
+        }) else { return Vec::new() };
+        self.removed_all(PoolEventKind::Remove, &extracted, indices.into_iter().rev(), emptied);
+        extracted
+    }
+
+    /// The values of `T` as two slices, in order. The second slice is only non-empty for types
+    /// in deque mode whose values wrap around
+    fn slices<T: 'static>(&self) -> Option<(&[T], &[T])> {
+        match self.type_deque::<T>() {
+            Some(deque) => Some(deque.as_slices()),
+            None => self.type_pool::<T>()
+                .map(|p| (p.values.as_slice(), &[][..])),
+        }
+    }
+
+    /// Iterate over the values of `T`, also for types in deque mode
+    fn iter_values<T: 'static>(&self) -> Option<impl DoubleEndedIterator<Item = &T> + ExactSizeIterator> {
+        match self.type_deque::<T>() {
+            Some(deque) => Some(ValuesIter::Deque(deque.iter())),
+            None => self.type_pool::<T>()
+                .map(|p| ValuesIter::Pool(p.values.iter())),
+        }
+    }
+
+    /// The values of `T` as a mutable slice. The values of a type in deque mode are made
+    /// contiguous first, see [`VecDeque::make_contiguous`]
+    fn slice_mut<T: 'static>(&mut self) -> Option<&mut [T]> {
+        if self.type_deque::<T>().is_some() {
+            return self.deque_values_mut::<T>()
+                .map(VecDeque::make_contiguous);
+        }
+        self.pool_mut::<T>()
+            .map(|p| p.values.as_mut_slice())
+    }
+
+    /// Call `f` with the values of `T` in a `Vec`, returning its result. The values of a type in
+    /// deque mode are moved to a `Vec` for the call and back to the deque afterwards, which is
+    /// O(n)
+    ///
+    /// Returns `None` if the type pool doesn't exist
+    fn with_values<T: 'static, R>(&mut self, f: impl FnOnce(&mut Vec<T>) -> R) -> Option<R> {
+        let (result, was_empty, is_empty) = match self.deque_mut::<T>() {
+            Some(deque) => {
+                let mut values = Vec::from(std::mem::take(&mut deque.values));
+                let was_empty = values.is_empty();
+                let result = f(&mut values);
+                let is_empty = values.is_empty();
+                deque.values = VecDeque::from(values);
+                (result, was_empty, is_empty)
+            }
+            None => {
+                let pool = self.pool_mut::<T>()?;
+                let was_empty = pool.values.is_empty();
+                let result = f(&mut pool.values);
+                pool.update_peak();
+                (result, was_empty, pool.values.is_empty())
+            }
+        };
+        self.empty_changed(was_empty, is_empty);
+        Some(result)
+    }
+
+    /// Like [`with_values`](Self::with_values), but creates the type pool if it doesn't exist yet
+    fn with_values_or_create<T: 'static, R>(&mut self, f: impl FnOnce(&mut Vec<T>) -> R) -> R {
+        if !self.contains_type::<T>() {
+            self.pool_or_create::<T>();
+        }
+        unsafe { self.with_values(f).unwrap_unchecked() } // safety: the type pool was created above if it didn't exist
+    }
+
+    /// Bookkeeping after a type pool was emptied
+    fn pool_emptied(&mut self) {
+        if self.auto_prune.is_some_and(|threshold| self.empty_count() >= threshold) {
+            self.remove_empty();
+        }
+    }
+
+    /// The amount of empty type pools, counting them again if the count isn't known
+    fn empty_count(&mut self) -> usize {
+        if let Some(count) = self.empty_types {
+            return count;
+        }
+        // the peaks may be outdated as well after the type pools were accessed directly
+        self.pools.values_mut().for_each(|pool| pool.update_peak());
+        *self.empty_types.insert(self.pools.values().filter(|pool| pool.is_empty()).count())
+    }
+
+    /// Update the count of empty type pools after a type pool that was empty if `was_empty` is
+    /// empty if `is_empty`. A missing type pool counts as neither, so adding an empty type pool
+    /// is `(false, true)` and removing it `(true, false)`
+    fn empty_changed(&mut self, was_empty: bool, is_empty: bool) {
+        if let Some(count) = &mut self.empty_types {
+            match (was_empty, is_empty) {
+                (false, true) => *count += 1,
+                (true, false) => *count -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// Record the mutations of the pools, which can be retrieved with
+    /// [`take_events`](Self::take_events)
+    ///
+    /// All values added, removed or replaced by the methods of `TypePools` and [`Batch`] are
+    /// recorded, see [`PoolEventKind`]. Not recorded are changes through references to values
+    /// or type pools, like the ones returned by `get_mut` and `type_pool_mut` or passed to the
+    /// closures of `merge_with` and `sweep_registered`, and type pools that are removed or moved
+    /// as a whole, like with `remove_type`, `migrate`, `move_pool`, `drain_into` and
+    /// `take_all`.
+    pub fn enable_recording(&mut self) {
+        self.events.get_or_insert_with(Vec::new);
+    }
+
+    /// Take the events recorded since recording was enabled or the previous call
+    pub fn take_events(&mut self) -> Vec<PoolEvent> {
+        self.events.as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Record an event for each of `indices` in the type pool of `T`
+    fn record<T: 'static>(&mut self, kind: PoolEventKind, indices: impl Iterator<Item = usize>) {
+        if let Some(events) = &mut self.events {
+            events.extend(indices.map(|index| PoolEvent { type_id: TypeId::of::<T>(), kind, index }));
+        }
+    }
+
+    /// Record that the values of the type pool of `T` were replaced by `new_len` other values
+    fn record_replaced<T: 'static>(&mut self, old_len: usize, new_len: usize) {
+        if old_len > 0 {
+            self.record::<T>(PoolEventKind::Clear, 0..1);
+        }
+        self.record::<T>(PoolEventKind::Push, 0..new_len);
+    }
+
+    /// Evict type pools according to `max_types` to make room for a new type pool
+    fn evict_for_new_type(&mut self) {
+        if let Some(max_types) = self.max_types {
+            self.evict_to(max_types.saturating_sub(1));
+        }
+    }
+
+    /// Evict type pools until at most `n` are left, see [`set_max_types`](Self::set_max_types)
+    fn evict_to(&mut self, n: usize) {
+        while self.pools.len() > n {
+            let victim = self.pools.iter()
+                .min_by_key(|(_, pool)| (!pool.is_empty(), pool.last_touch()))
+                .map(|(id, _)| *id);
+            match victim {
+                Some(id) => self.remove_pool(id),
+                None => break,
+            }
+        }
+    }
+
+
+    /// Create an empty type pool, with the capacity hinted by
+    /// [`hint_capacity`](Self::hint_capacity)
+    fn new_pool<T: 'static>(&mut self) -> TypePool<T> {
+        let mut pool = TypePool::<T>::new();
+        if let Some(capacity) = self.capacity_hints.remove(&TypeId::of::<T>()) {
+            pool.values.reserve_exact(capacity);
+        }
+        pool
+    }
+
+    fn observers_mut<T: 'static>(&mut self) -> &mut Observers<T> {
+        let observers = self.observers.entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Observers::<T>::new()));
+        unsafe { observers.downcast_mut().unwrap_unchecked() } // safety: observers are stored under the id of their type
+    }
+
+    fn notify_push<T: 'static>(&mut self, value: &T) {
+        if let Some(observers) = self.observers.get_mut(&TypeId::of::<T>()) {
+            let observers = unsafe { observers.downcast_mut::<Observers<T>>().unwrap_unchecked() }; // safety: observers are stored under the id of their type
+            observers.on_push.iter_mut().for_each(|f| f(value));
+        }
+    }
+
+    fn notify_remove<T: 'static>(&mut self, value: &T) {
+        if let Some(observers) = self.observers.get_mut(&TypeId::of::<T>()) {
+            let observers = unsafe { observers.downcast_mut::<Observers<T>>().unwrap_unchecked() }; // safety: observers are stored under the id of their type
+            observers.on_remove.iter_mut().for_each(|f| f(value));
+        }
+    }
+
+    fn notify_empty<T: 'static>(&mut self) {
+        if let Some(observers) = self.observers.get_mut(&TypeId::of::<T>()) {
+            let observers = unsafe { observers.downcast_mut::<Observers<T>>().unwrap_unchecked() }; // safety: observers are stored under the id of their type
+            observers.on_empty.iter_mut().for_each(|f| f());
+        }
+    }
+
+    /// Gets a value from a TypePool
+    ///
+    /// # Parameters
+    /// - idx: this is the index in the specific type `T` array
+    pub fn get<T: 'static>(&self, idx: usize) -> Option<&T> {
+        match self.type_deque::<T>() {
+            Some(deque) => deque.get(idx),
+            None => self.type_pool()
+                .and_then(|p| p.values.get(idx)),
+        }
+    }
+
+    /// Gets a clone of a value from a TypePool, so `self` isn't borrowed afterwards
+    pub fn get_cloned<T: 'static + Clone>(&self, idx: usize) -> Option<T> {
+        self.get::<T>(idx).cloned()
+    }
+
+    /// Get a mutable reference to a value in a TypePool
+    pub fn get_mut<T: 'static>(&mut self, idx: usize) -> Option<&mut T> {
+        if self.type_deque::<T>().is_some() {
+            return self.deque_values_mut::<T>()?.get_mut(idx);
+        }
+        self.pool_mut()
+            .and_then(|p| p.values.get_mut(idx))
+    }
+
+    /// Call `f` with a mutable reference to a value in a TypePool, returning its result
+    ///
+    /// Returns `None` if the type pool doesn't exist or `idx` is out of bounds
+    pub fn with_element_mut<T: 'static, R, F: FnOnce(&mut T) -> R>(&mut self, idx: usize, f: F) -> Option<R> {
+        self.get_mut::<T>(idx)
+            .map(f)
+    }
+
+    /// Get a mutable reference to a value in a TypePool. If `idx` is the length of the type
+    /// pool, `default` is pushed first
+    ///
+    /// Returns `None` if `idx` is larger than the length of the type pool
+    pub fn get_mut_or_insert<T: 'static>(&mut self, idx: usize, default: T) -> Option<&mut T> {
+        match self.len_or_zero::<T>() {
+            len if idx < len => self.get_mut(idx),
+            len if idx == len => {
+                self.push(default);
+                self.get_mut(idx)
+            }
+            _ => None,
+        }
+    }
+
+    /// Gets the only value of a TypePool, or `None` if it doesn't contain exactly one value
+    pub fn single<T: 'static>(&self) -> Option<&T> {
+        match self.slices::<T>()? {
+            ([value], []) | ([], [value]) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get a mutable reference to the only value of a TypePool, or `None` if it doesn't
+    /// contain exactly one value
+    pub fn single_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        match self.slice_mut::<T>()? {
+            [value] => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Gets a value from a TypePool by its typed index
+    pub fn get_indexed<T: 'static>(&self, idx: PoolIndex<T>) -> Option<&T> {
+        self.get(idx.0)
+    }
+
+    /// Clones the values of a TypePool into a vector, which is empty if the type pool doesn't
+    /// exist
+    pub fn collect<T: 'static + Clone>(&self) -> Vec<T> {
+        self.iter_values::<T>()
+            .map_or_else(Vec::new, |values| values.cloned().collect())
+    }
+
+    /// Clones the values of a TypePool for which `pred` returns `true` into a vector
+    pub fn collect_filtered<T: 'static + Clone, F: FnMut(&T) -> bool>(&self, mut pred: F) -> Vec<T> {
+        self.iter_values::<T>()
+            .map_or_else(Vec::new, |values| values.filter(|v| pred(v)).cloned().collect())
+    }
+
+    /// Gets two values from a TypePool. `i` and `j` may be equal
+    pub fn get_two<T: 'static>(&self, i: usize, j: usize) -> Option<(&T, &T)> {
+        Some((self.get::<T>(i)?, self.get::<T>(j)?))
+    }
+
+    /// Gets the values at every index of `indices`, which may contain the same index more than
+    /// once. Every value is `None` if the type pool doesn't exist
+    pub fn get_many<T: 'static>(&self, indices: &[usize]) -> Vec<Option<&T>> {
+        let (front, back) = self.slices::<T>().unwrap_or_default();
+        indices.iter()
+            .map(|&idx| front.get(idx).or_else(|| back.get(idx.checked_sub(front.len())?)))
+            .collect()
+    }
+
+    /// Split the values of the type pool into two slices at `mid`
+    ///
+    /// # Panics
+    /// If `mid` is larger than the length of the type pool
+    pub fn split_at<T: 'static>(&self, mid: usize) -> Option<(&[T], &[T])> {
+        self.type_pool::<T>()
+            .map(|p| p.values.split_at(mid))
+    }
+
+    /// Split the values of the type pool into two mutable slices at `mid`
+    ///
+    /// # Panics
+    /// If `mid` is larger than the length of the type pool
+    pub fn split_at_mut<T: 'static>(&mut self, mid: usize) -> Option<(&mut [T], &mut [T])> {
+        self.slice_mut::<T>()
+            .map(|values| values.split_at_mut(mid))
+    }
+
+    /// The first `n` values of the type pool, or all values if there are fewer than `n`
+    pub fn first_n<T: 'static>(&self, n: usize) -> Option<&[T]> {
+        self.type_pool::<T>()
+            .map(|p| &p.values[..n.min(p.values.len())])
+    }
+
+    /// The last `n` values of the type pool, or all values if there are fewer than `n`
+    pub fn last_n<T: 'static>(&self, n: usize) -> Option<&[T]> {
+        self.type_pool::<T>()
+            .map(|p| &p.values[p.values.len() - n.min(p.values.len())..])
+    }
+
+    /// Iterate over every unordered pair of values of the type pool, as `(&values[i], &values[j])`
+    /// for every `i < j`
+    pub fn pairs<T: 'static>(&self) -> Option<impl Iterator<Item = (&T, &T)>> {
+        self.type_pool::<T>()
+            .map(|p| {
+                let values = &p.values[..];
+                (0..values.len()).flat_map(move |i| values[i + 1..].iter().map(move |b| (&values[i], b)))
+            })
+    }
+
+    /// Iterate over chunks of exactly `n` values of the type pool. The remaining values are
+    /// available through [`ChunksExact::remainder`](std::slice::ChunksExact::remainder)
+    ///
+    /// # Panics
+    /// If `n` is 0
+    pub fn chunks_exact<T: 'static>(&self, n: usize) -> Option<std::slice::ChunksExact<'_, T>> {
+        self.type_pool::<T>()
+            .map(|p| p.values.chunks_exact(n))
+    }
+
+    /// Iterate over chunks of `n` values of the type pool, starting at the end. The last chunk
+    /// is shorter if the length isn't divisible by `n`
+    ///
+    /// # Panics
+    /// If `n` is 0
+    pub fn rchunks<T: 'static>(&self, n: usize) -> Option<std::slice::RChunks<'_, T>> {
+        self.type_pool::<T>()
+            .map(|p| p.values.rchunks(n))
+    }
+
+    /// Gets a value from a TypePool, returning why it couldn't be retrieved on failure
+    pub fn checked_get<T: 'static>(&self, idx: usize) -> Result<&T, PoolError> {
+        let len = self.len::<T>().ok_or(PoolError::TypeNotFound)?;
+        self.get::<T>(idx).ok_or(PoolError::IndexOutOfBounds { idx, len })
+    }
+
+    /// Get a mutable reference to a value in a TypePool, returning why it couldn't be
+    /// retrieved on failure
+    pub fn checked_get_mut<T: 'static>(&mut self, idx: usize) -> Result<&mut T, PoolError> {
+        let len = self.len::<T>().ok_or(PoolError::TypeNotFound)?;
+        self.get_mut::<T>(idx).ok_or(PoolError::IndexOutOfBounds { idx, len })
+    }
+
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len<T: 'static>(&self) -> Option<usize> {
+        self.pools.get(&TypeId::of::<T>())
+            .map(|pool| pool.len())
+    }
+
+    /// The range of valid indices of the type pool
+    pub fn indices<T: 'static>(&self) -> Option<Range<usize>> {
+        self.len::<T>()
+            .map(|len| 0..len)
+    }
+
+    /// The amount of values in the type pool, or 0 if the type pool doesn't exist
+    pub fn len_or_zero<T: 'static>(&self) -> usize {
+        self.len::<T>().unwrap_or(0)
+    }
+
+    /// Returns `true` if the type pool doesn't exist or contains no values
+    pub fn is_type_empty<T: 'static>(&self) -> bool {
+        self.len_or_zero::<T>() == 0
+    }
+
+    /// Returns `true` if the type pool exists and `idx` is in bounds
+    pub fn contains_index<T: 'static>(&self, idx: usize) -> bool {
+        self.len::<T>()
+            .is_some_and(|len| idx < len)
+    }
+
+    /// Returns `true` if the type pool exists and contains at least one value
+    pub fn has_nonempty_type<T: 'static>(&self) -> bool {
+        !self.is_type_empty::<T>()
+    }
+
+    /// The capacity of the type pool, or 0 if the type pool doesn't exist
+    pub fn capacity_or_zero<T: 'static>(&self) -> usize {
+        self.pools.get(&TypeId::of::<T>())
+            .map_or(0, |pool| pool.capacity())
+    }
+
+    /// Returns the first value in the type pool for which `pred` returns `true`, together with
+    /// its index
+    pub fn find_with_index<T: 'static, F: FnMut(&T) -> bool>(&self, mut pred: F) -> Option<(usize, &T)> {
+        self.iter_values::<T>()?
+            .enumerate()
+            .find(|(_, value)| pred(value))
+    }
+
+    /// Returns the index of the last value in the type pool for which `pred` returns `true`
+    pub fn rposition<T: 'static, F: FnMut(&T) -> bool>(&self, pred: F) -> Option<usize> {
+        self.iter_values::<T>()?
+            .rposition(pred)
+    }
+
+    /// Returns the last value in the type pool for which `pred` returns `true`
+    pub fn rfind<T: 'static, F: FnMut(&&T) -> bool>(&self, pred: F) -> Option<&T> {
+        self.iter_values::<T>()?
+            .rfind(pred)
+    }
+
+    /// Combine all values of the type pool with `f`, or `None` if the type pool is empty. With a
+    /// single value, a clone of it is returned
+    pub fn reduce<T: 'static + Clone, F: FnMut(&T, &T) -> T>(&self, mut f: F) -> Option<T> {
+        let mut values = self.iter_values::<T>()?;
+        let first = values.next()?.clone();
+        Some(values.fold(first, |acc, value| f(&acc, value)))
+    }
+
+    /// The smallest value of the type pool, or `None` if it is empty
+    pub fn min<T: 'static + Ord>(&self) -> Option<&T> {
+        self.iter_values::<T>()?.min()
+    }
+
+    /// The largest value of the type pool, or `None` if it is empty
+    pub fn max<T: 'static + Ord>(&self) -> Option<&T> {
+        self.iter_values::<T>()?.max()
+    }
+
+    /// The value of the type pool with the smallest key returned by `f`, or `None` if it is empty
+    pub fn min_by_key<T: 'static, K: Ord, F: FnMut(&T) -> K>(&self, mut f: F) -> Option<&T> {
+        self.iter_values::<T>()?.min_by_key(|value| f(value))
+    }
+
+    /// The value of the type pool with the largest key returned by `f`, or `None` if it is empty
+    pub fn max_by_key<T: 'static, K: Ord, F: FnMut(&T) -> K>(&self, mut f: F) -> Option<&T> {
+        self.iter_values::<T>()?.max_by_key(|value| f(value))
+    }
+
+    /// A hash of the values of the type pool in order, to detect whether they changed. The
+    /// hash is stable within a single run of the program
+    pub fn checksum<T: 'static + Hash>(&self) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        match self.type_deque::<T>() {
+            Some(deque) => deque.hash(&mut hasher),
+            None => self.type_pool::<T>()?.values.hash(&mut hasher),
+        }
+        Some(hasher.finish())
+    }
+
+    /// Count the values of the type pool per key returned by `key_fn`. Returns an empty map if
+    /// the type pool doesn't exist
+    pub fn histogram<T: 'static, K: Eq + Hash, F: FnMut(&T) -> K>(&self, mut key_fn: F) -> HashMap<K, usize> {
+        let mut counts = HashMap::new();
+        for value in self.iter_values::<T>().into_iter().flatten() {
+            *counts.entry(key_fn(value)).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Returns `true` if every value of `values` is in the type pool. This is `true` for empty
+    /// `values`, even if the type pool doesn't exist
+    pub fn contains_all<T: 'static + PartialEq>(&self, values: &[T]) -> bool {
+        let (front, back) = self.slices::<T>().unwrap_or_default();
+        values.iter().all(|value| front.contains(value) || back.contains(value))
+    }
+
+    /// Returns `true` if any value of `values` is in the type pool
+    pub fn contains_any<T: 'static + PartialEq>(&self, values: &[T]) -> bool {
+        let (front, back) = self.slices::<T>().unwrap_or_default();
+        values.iter().any(|value| front.contains(value) || back.contains(value))
+    }
+
+    /// Returns whether the values of the type pool are sorted in non-decreasing order
+    pub fn is_sorted<T: 'static + Ord>(&self) -> Option<bool> {
+        self.iter_values::<T>()
+            .map(Iterator::is_sorted)
+    }
+
+    /// A pointer to the buffer of the type pool. Can be used to check whether the type pool
+    /// was reallocated
+    pub fn pool_data_ptr<T: 'static>(&self) -> Option<*const T> {
+        self.type_pool::<T>()
+            .map(|p| p.values.as_ptr())
+    }
+
+    /// Returns the index of the first value in the type pool equal to `value`
+    pub fn first_index_of<T: 'static + PartialEq>(&self, value: &T) -> Option<usize> {
+        self.iter_values::<T>()?
+            .position(|v| v == value)
+    }
+
+    /// Returns the index of the last value in the type pool equal to `value`
+    pub fn last_index_of<T: 'static + PartialEq>(&self, value: &T) -> Option<usize> {
+        self.iter_values::<T>()?
+            .rposition(|v| v == value)
+    }
+
+    /// Run `f` over the whole mutable slice of the type pool, returning its result
+    pub fn apply<T: 'static, R, F: FnOnce(&mut [T]) -> R>(&mut self, f: F) -> Option<R> {
+        self.slice_mut::<T>()
+            .map(f)
+    }
+
+    /// Run `f` on every value in the type pool
+    pub fn map_in_place<T: 'static, F: FnMut(&mut T)>(&mut self, f: F) -> Option<()> {
+        self.slice_mut::<T>()
+            .map(|values| values.iter_mut().for_each(f))
+    }
+
+    /// Run `f` on every value in the type pool together with its index
+    pub fn for_each_mut_indexed<T: 'static, F: FnMut(usize, &mut T)>(&mut self, mut f: F) -> Option<()> {
+        self.slice_mut::<T>()
+            .map(|values| values.iter_mut().enumerate().for_each(|(idx, value)| f(idx, value)))
+    }
+
+    /// Run `f` on every value in the type pool, stopping at and returning the first error.
+    /// Returns `Ok(None)` if the type pool doesn't exist
+    pub fn try_for_each_mut<T: 'static, E, F: FnMut(&mut T) -> Result<(), E>>(&mut self, f: F) -> Result<Option<()>, E> {
+        self.slice_mut::<T>()
+            .map(|values| values.iter_mut().try_for_each(f))
+            .transpose()
+    }
+
+    /// The amount of types stored in pools
+    pub fn types_count(&self) -> usize {
+        self.pools.keys().len()
+    }
+
+    /// The amount of values in all type pools
+    pub fn total_len(&self) -> usize {
+        self.pools.values()
+            .map(|pool| pool.len())
+            .sum()
+    }
+
+    /// Returns `true` if any type pool contains at least one value. Stops at the first
+    /// non-empty type pool, which makes this cheaper than `total_len() > 0`
+    pub fn any_nonempty(&self) -> bool {
+        self.pools.values()
+            .any(|pool| !pool.is_empty())
+    }
+
+    /// Returns `true` if every non-empty type pool has the same length, as expected for type
+    /// pools used as parallel arrays
+    pub fn all_lengths_equal(&self) -> bool {
+        let mut lengths = self.pools.values()
+            .map(|pool| pool.len())
+            .filter(|len| *len > 0);
+        let first = lengths.next();
+        lengths.all(|len| Some(len) == first)
+    }
+
+    /// The length of the longest type pool, or 0 if there are no type pools
+    pub fn max_len(&self) -> usize {
+        self.pools.values()
+            .map(|pool| pool.len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns `true` if every type in `ids` has a type pool with at least one value
+    pub fn all_present_nonempty(&self, ids: &[TypeId]) -> bool {
+        ids.iter()
+            .all(|id| self.pools.get(id).is_some_and(|pool| !pool.is_empty()))
+    }
+
+    /// The amount of types for which `pred` returns `true`
+    pub fn count_types<F: FnMut(TypeId) -> bool>(&self, mut pred: F) -> usize {
+        self.pools.keys()
+            .filter(|id| pred(**id))
+            .count()
+    }
+
+    /// The amount of types with at least one value
+    pub fn nonempty_type_count(&self) -> usize {
+        self.pools.values()
+            .filter(|pool| !pool.is_empty())
+            .count()
+    }
+
+    /// The amount of types whose type pool is empty
+    pub fn empty_type_count(&self) -> usize {
+        self.empty_types.unwrap_or_else(|| self.types_count() - self.nonempty_type_count())
+    }
+
+    /// Returns `true` if there is a type pool for `T`, even if it's empty
+    pub fn contains_type<T: 'static>(&self) -> bool {
+        self.pools.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Create an empty type pool for `T` if it doesn't exist yet, so that
+    /// [`type_pool`](Self::type_pool) returns `Some` before any value was pushed
+    pub fn register_empty<T: 'static>(&mut self) {
+        if !self.contains_type::<T>() {
+            self.pool_or_create::<T>();
+        }
+    }
+
+    /// Returns `true` if there is a type pool for `T`, see
+    /// [`register_empty`](Self::register_empty). Same as [`contains_type`](Self::contains_type)
+    pub fn is_registered<T: 'static>(&self) -> bool {
+        self.contains_type::<T>()
+    }
+
+    /// The types stored in pools
+    pub fn types(&self) -> Vec<&TypeId> {
+        self.pools.keys().collect()
+    }
+
+    /// The types stored in pools in a stable order, which doesn't depend on the order in which
+    /// the type pools were created
+    pub fn sorted_type_ids(&self) -> Vec<TypeId> {
+        let mut ids: Vec<TypeId> = self.pools.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// The types stored in pools together with the amount of values of each type
+    pub fn iter_types(&self) -> impl Iterator<Item = (TypeId, usize)> + '_ {
+        self.pools.iter()
+            .map(|(id, pool)| (*id, pool.len()))
+    }
+
+    /// The type, length and capacity of every type pool
+    pub fn capacity_report(&self) -> Vec<(TypeId, usize, usize)> {
+        self.pools.iter()
+            .map(|(id, pool)| (*id, pool.len(), pool.capacity()))
+            .collect()
+    }
+
+    /// Keep only the first `len` values of the type pool, returning the removed values. Returns
+    /// an empty `Vec` if the type pool doesn't exist or isn't longer than `len`
+    pub fn truncate_returning<T: 'static>(&mut self, len: usize) -> Vec<T> {
+        let old_len = self.len_or_zero::<T>();
+        if old_len <= len {
+            return Vec::new();
+        }
+        if let Some(pool) = self.pool_mut::<T>() {
+            pool.peak_len = pool.peak_len.max(old_len);
+        }
+        let removed = unsafe { self.with_values::<T, _>(|values| values.split_off(len)).unwrap_unchecked() }; // safety: the type pool isn't empty, so it exists
+        self.removed_all(PoolEventKind::Remove, &removed, (len..old_len).rev(), len == 0);
+        removed
+    }
+
+    /// Keep only the first `len` values of every type pool
+    pub fn truncate_all(&mut self, len: usize) {
+        for (id, pool) in self.pools.iter_mut() {
+            if let Some(events) = &mut self.events {
+                events.extend((len..pool.len()).rev().map(|index| PoolEvent { type_id: *id, kind: PoolEventKind::Remove, index }));
+            }
+            pool.truncate(len);
+        }
+        if len == 0 {
+            self.empty_types = Some(self.pools.len());
+        }
+    }
+
+    /// The length of every type pool, to roll back appends with
+    /// [`truncate_to_lengths`](Self::truncate_to_lengths)
+    pub fn snapshot_lengths(&self) -> HashMap<TypeId, usize> {
+        self.pools.iter()
+            .map(|(id, pool)| (*id, pool.len()))
+            .collect()
+    }
+
+    /// Truncate every type pool to its length in `snapshot`, as returned by
+    /// [`snapshot_lengths`](Self::snapshot_lengths). Type pools that aren't in the snapshot
+    /// are removed
+    ///
+    /// This only restores the previous state if values were only appended since the snapshot.
+    pub fn truncate_to_lengths(&mut self, snapshot: &HashMap<TypeId, usize>) {
+        self.empty_types = None;
+        let events = &mut self.events;
+        self.pools.retain(|id, pool| match snapshot.get(id) {
+            Some(&len) => {
+                if let Some(events) = events {
+                    events.extend((len..pool.len()).rev().map(|index| PoolEvent { type_id: *id, kind: PoolEventKind::Remove, index }));
+                }
+                pool.truncate(len);
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// The types of the type pools with a capacity of more than `elements` values
+    pub fn pools_over_capacity(&self, elements: usize) -> Vec<TypeId> {
+        self.pools.iter()
+            .filter(|(_, pool)| pool.capacity() > elements)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Remove all entries for a type, dropping the type pool itself. Afterwards
+    /// [`type_pool`](Self::type_pool) returns `None`, see [`empty_type`](Self::empty_type) to
+    /// keep the type pool
+    pub fn remove_type<T: 'static>(&mut self) {
+        self.remove_pool(TypeId::of::<T>());
+    }
+
+    /// Remove the type pool of the type with `id`, if it exists
+    fn remove_pool(&mut self, id: TypeId) {
+        if let Some(pool) = self.pools.remove(&id) {
+            self.empty_changed(pool.is_empty(), false);
+        }
+    }
+
+    /// Remove all values of a type, but keep its (empty) type pool and its capacity. Afterwards
+    /// [`type_pool`](Self::type_pool) still returns `Some`, see
+    /// [`remove_type`](Self::remove_type) to drop the type pool
+    pub fn empty_type<T: 'static>(&mut self) {
+        let Some(pool) = self.pools.get_mut(&TypeId::of::<T>()) else { return };
+        let had_values = !pool.is_empty();
+        pool.clear();
+
+        if had_values {
+            self.empty_changed(false, true);
+            if self.events.is_some() {
+                self.record::<T>(PoolEventKind::Clear, 0..1);
+            }
+        }
+    }
+
+    /// Call `f` with every type pool
+    pub fn for_each_pool_mut<F: FnMut(TypeId, &mut dyn TypePoolTrait)>(&mut self, mut f: F) {
+        self.empty_types = None;
+        for (id, pool) in self.pools.iter_mut() {
+            f(*id, pool.as_mut());
+        }
+    }
+
+    /// Call `f` with every type pool, removing the type pools for which it returns `false`.
+    /// `f` can change the values of a type pool after downcasting it through
+    /// [`TypePoolTrait::as_any_mut`]
+    pub fn gc<F: FnMut(TypeId, &mut dyn TypePoolTrait) -> bool>(&mut self, mut f: F) {
+        self.empty_types = None;
+        self.pools.retain(|id, pool| f(*id, pool.as_mut()));
+    }
+
+    /// Remove all types which do not contain any values
+    pub fn remove_empty(&mut self) {
+        let to_remove = self.pools.keys()
+            // safety: the keys exist
+            .filter(|key| unsafe{ self.pools.get(key).unwrap_unchecked() }.is_empty())
+            .copied()
+            .collect::<Vec<TypeId>>();
+
+        for id in to_remove {
+            self.pools.remove(&id);
+        }
+        if self.empty_types.is_none() {
+            self.pools.values_mut().for_each(|pool| pool.update_peak());
+        }
+        self.empty_types = Some(0);
+    }
+
+    /// Shrink the array containing all the pools to fit
+    pub fn shrink_to_fit(&mut self) {
+        self.pools.shrink_to_fit()
+    }
+
+    /// Shrink the type pool to fit only if its capacity is more than `ratio` times its length
+    ///
+    /// Returns whether the type pool was shrunk
+    pub fn shrink_if_oversized<T: 'static>(&mut self, ratio: f32) -> Option<bool> {
+        self.pools.get_mut(&TypeId::of::<T>())
+            .map(|pool| {
+                let oversized = pool.capacity() as f32 > pool.len() as f32 * ratio;
+                if oversized {
+                    pool.shrink_to_fit();
+                }
+                oversized
+            })
+    }
+
+    /// Shrink the array containing all the pools to fit if it is more than twice as large as
+    /// needed, ignoring small differences
+    pub fn maybe_shrink(&mut self) {
+        const SLACK: usize = 16;
+        let (capacity, len) = (self.pools.capacity(), self.pools.len());
+        if capacity > len * 2 && capacity - len > SLACK {
+            self.pools.shrink_to_fit();
+        }
+    }
+
+    /// Shrink the capacity of every type pool to fit its values
+    pub fn shrink_all(&mut self) {
+        for pool in self.pools.values_mut() {
+            pool.shrink_to_fit();
+        }
+    }
+
+    /// The amount of bytes allocated for the values of all type pools
+    pub fn memory_usage(&self) -> usize {
+        self.pools.values()
+            .map(|pool| pool.memory_usage())
+            .sum()
+    }
+
+    /// The bytes allocated for all type pools, split into bytes holding values, bytes reserved
+    /// for future values and an estimate of the bytes of the map containing the type pools
+    pub fn memory_breakdown(&self) -> MemoryBreakdown {
+        let (used, reserved) = self.pools.values()
+            .map(|pool| (pool.len() * pool.element_size(), (pool.capacity() - pool.len()) * pool.element_size()))
+            .fold((0, 0), |(used, reserved), (u, r)| (used + u, reserved + r));
+        let map_overhead = self.pools.capacity() * std::mem::size_of::<(TypeId, Box<dyn TypePoolTrait>)>();
+        MemoryBreakdown { used, reserved, map_overhead }
+    }
+
+    /// Release as much memory as possible: removes empty type pools and shrinks every type
+    /// pool and the array containing them to fit
+    pub fn compact(&mut self) -> CompactReport {
+        let before = self.memory_usage();
+        self.remove_empty();
+        self.shrink_all();
+        self.shrink_to_fit();
+        CompactReport { before, after: self.memory_usage() }
+    }
+
+    /// Release the memory of a single type pool: removes it if it is empty, otherwise shrinks
+    /// it to fit
+    ///
+    /// Returns whether the type pool was removed
+    pub fn compact_type<T: 'static>(&mut self) -> bool {
+        match self.pools.get_mut(&TypeId::of::<T>()) {
+            Some(pool) if pool.is_empty() => {
+                self.remove_type::<T>();
+                true
+            }
+            Some(pool) => {
+                pool.shrink_to_fit();
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// Metrics about the type pools of a collection, so exporters don't depend on [`TypePools`]
+pub trait PoolMetrics {
+    /// The amount of type pools
+    fn type_count(&self) -> usize;
+    /// The amount of values in all type pools
+    fn total_values(&self) -> usize;
+    /// The type name, length and capacity of every type pool
+    fn pool_metrics(&self) -> Box<dyn Iterator<Item = (&'static str, usize, usize)> + '_>;
+}
+
+impl PoolMetrics for TypePools {
+    fn type_count(&self) -> usize {
+        self.types_count()
+    }
+
+    fn total_values(&self) -> usize {
+        self.total_len()
+    }
+
+    fn pool_metrics(&self) -> Box<dyn Iterator<Item = (&'static str, usize, usize)> + '_> {
+        Box::new(self.pools.values().map(|pool| (pool.type_name(), pool.len(), pool.capacity())))
+    }
+}
+
+/// A read-only view of [`TypePools`], created by [`TypePools::as_view`]
+///
+/// Only the querying methods are available:
+///
+/// ```compile_fail
+/// # use type_pools::TypePools;
+/// let mut pools = TypePools::new();
+/// pools.as_view().push(1u32);
+/// ```
+#[derive(Clone, Copy)]
+pub struct TypePoolsRef<'a> {
+    pools: &'a TypePools,
+}
+
+impl<'a> TypePoolsRef<'a> {
+    /// Get a reference to a type pool
+    pub fn type_pool<T: 'static>(&self) -> Option<&'a TypePool<T>> {
+        self.pools.type_pool()
+    }
+
+    /// Gets a value from a TypePool
+    pub fn get<T: 'static>(&self, idx: usize) -> Option<&'a T> {
+        self.pools.get(idx)
+    }
+
+    /// Iterate over the values of a type pool
+    pub fn iter<T: 'static>(&self) -> Option<std::slice::Iter<'a, T>> {
+        self.pools.type_pool::<T>()
+            .map(|p| p.values.iter())
+    }
+
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len<T: 'static>(&self) -> Option<usize> {
+        self.pools.len::<T>()
+    }
+
+    /// The amount of types stored in pools
+    pub fn types_count(&self) -> usize {
+        self.pools.types_count()
+    }
+
+    /// The types stored in pools
+    pub fn types(&self) -> Vec<&'a TypeId> {
+        self.pools.types()
+    }
+}
+
+/// A reference to a type pool returned by [`TypePools::tracked_type_pool`]
+#[cfg(feature = "debug-tracking")]
+pub struct TrackedPool<'a, T> {
+    pool: &'a TypePool<T>,
+    borrows: &'a Cell<usize>,
+}
+
+#[cfg(feature = "debug-tracking")]
+impl<'a, T> Deref for TrackedPool<'a, T> {
+    type Target = TypePool<T>;
+
+    fn deref(&self) -> &TypePool<T> {
+        self.pool
+    }
+}
+
+#[cfg(feature = "debug-tracking")]
+impl<'a, T> Drop for TrackedPool<'a, T> {
+    fn drop(&mut self) {
+        self.borrows.set(self.borrows.get() - 1);
+    }
+}
+
+/// A mutable reference to a type pool returned by [`TypePools::tracked_type_pool_mut`]
+#[cfg(feature = "debug-tracking")]
+pub struct TrackedPoolMut<'a, T> {
+    pool: &'a mut TypePool<T>,
+    borrows: &'a Cell<usize>,
+}
+
+#[cfg(feature = "debug-tracking")]
+impl<'a, T> Deref for TrackedPoolMut<'a, T> {
+    type Target = TypePool<T>;
+
+    fn deref(&self) -> &TypePool<T> {
+        self.pool
+    }
+}
+
+#[cfg(feature = "debug-tracking")]
+impl<'a, T> DerefMut for TrackedPoolMut<'a, T> {
+    fn deref_mut(&mut self) -> &mut TypePool<T> {
+        self.pool
+    }
+}
+
+#[cfg(feature = "debug-tracking")]
+impl<'a, T> Drop for TrackedPoolMut<'a, T> {
+    fn drop(&mut self) {
+        self.borrows.set(self.borrows.get() - 1);
+    }
+}
+
+/// An index into the type pool of `T`, returned by [`TypePools::push_indexed`]
+///
+/// It can only be used to get values of `T`:
+///
+/// ```compile_fail
+/// # use type_pools::TypePools;
+/// let mut pools = TypePools::new();
+/// let idx = pools.push_indexed(1u32);
+/// pools.get_indexed::<String>(idx);
+/// ```
+pub struct PoolIndex<T>(usize, PhantomData<fn() -> T>);
+
+impl<T> PoolIndex<T> {
+    /// The index in the type pool
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl<T> Clone for PoolIndex<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for PoolIndex<T> {}
+
+impl<T> PartialEq for PoolIndex<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T> Eq for PoolIndex<T> {}
+
+impl<T> fmt::Debug for PoolIndex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PoolIndex").field(&self.0).finish()
+    }
+}
+
+/// How type pools grow, see [`TypePools::set_growth_policy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GrowthPolicy {
+    /// Let the type pools over-allocate, making pushes amortized O(1)
+    #[default]
+    Amortized,
+    /// Only reserve space for the values being added. Saves memory, but every push may
+    /// reallocate
+    Exact,
+}
+
+/// Whether [`TypePools::retain_update`] keeps a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Yes,
+    No,
+}
+
+/// A mutation of [`TypePools`], recorded after [`TypePools::enable_recording`]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolEvent {
+    /// The type of the mutated type pool
+    pub type_id: TypeId,
+    pub kind: PoolEventKind,
+    /// The index of the value in the type pool
+    pub index: usize,
+}
+
+/// The kind of a [`PoolEvent`]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolEventKind {
+    /// A value was added at the index
+    Push,
+    /// The value at the index was removed
+    Remove,
+    /// The value at the index was removed by moving the last value into its place, like
+    /// [`Vec::swap_remove`]
+    SwapRemove,
+    /// The value at the index was replaced
+    Set,
+    /// All values of the type pool were removed, the index is always 0
+    Clear,
+}
+
+/// The reason a value couldn't be retrieved from [`TypePools`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolError {
+    /// There is no type pool for the type
+    TypeNotFound,
+    /// The index is not smaller than the length of the type pool
+    IndexOutOfBounds { idx: usize, len: usize },
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::TypeNotFound => write!(f, "no type pool exists for the type"),
+            PoolError::IndexOutOfBounds { idx, len } => write!(f, "index {} is out of bounds for a type pool of length {}", idx, len),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+/// A type pool was stored under the `TypeId` of a different type, see
+/// [`TypePools::try_type_pool`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DowncastError {
+    /// The name of the requested type
+    pub expected: &'static str,
+    /// The name of the type of the values in the stored type pool
+    pub found: &'static str,
+}
+
+impl fmt::Display for DowncastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected a type pool of {}, found a type pool of {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for DowncastError {}
+
+/// The types whose type pools can be cloned by [`TypePools::clone_registered_into`]
+pub struct CloneRegistry {
+    cloners: HashMap<TypeId, fn(&TypePools, &mut TypePools)>,
+}
+
+impl Default for CloneRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CloneRegistry {
+    pub fn new() -> Self {
+        CloneRegistry { cloners: HashMap::new() }
+    }
+
+    /// Register `T` as clonable
+    pub fn register<T: 'static + Clone>(&mut self) {
+        self.cloners.insert(TypeId::of::<T>(), Self::clone_into::<T>);
+    }
+
+    fn clone_into<T: 'static + Clone>(src: &TypePools, dst: &mut TypePools) {
+        if let Some(pool) = src.type_pool::<T>() {
+            dst.extend(pool.values.iter().cloned());
+        } else if let Some(deque) = src.type_deque::<T>() {
+            if !dst.contains_type::<T>() {
+                dst.make_deque::<T>();
+            }
+            dst.extend(deque.iter().cloned());
+        }
+    }
+}
+
+/// Removes values from a type pool, returning them as `Vec<T>`
+type Sweeper = Box<dyn FnMut(&mut dyn TypePoolTrait) -> Box<dyn Any>>;
+
+/// Predicates per type deciding which values [`TypePools::sweep_registered`] removes
+pub struct SweepRegistry {
+    sweepers: HashMap<TypeId, Sweeper>,
+}
+
+impl Default for SweepRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SweepRegistry {
+    pub fn new() -> Self {
+        SweepRegistry { sweepers: HashMap::new() }
+    }
+
+    /// Register `pred` to decide which values of `T` are removed, replacing the previous
+    /// predicate of `T`
+    pub fn register<T: 'static, F: FnMut(&T) -> bool + 'static>(&mut self, mut pred: F) {
+        self.sweepers.insert(TypeId::of::<T>(), Box::new(move |pool: &mut dyn TypePoolTrait| {
+            if let Some(deque) = TypeDeque::<T>::cast_mut(pool) {
+                let (removed, kept): (Vec<T>, Vec<T>) = std::mem::take(&mut deque.values)
+                    .into_iter()
+                    .partition(|value| pred(value));
+                deque.values = VecDeque::from(kept);
+                return Box::new(removed);
+            }
+            let pool = TypePool::<T>::cast_mut(pool).expect("sweepers are only called with the pool stored under their id");
+            let (removed, kept): (Vec<T>, Vec<T>) = std::mem::take(&mut pool.values)
+                .into_iter()
+                .partition(|value| pred(value));
+            pool.values = kept;
+            Box::new(removed)
+        }));
+    }
+}
+
+/// Calls a typed visitor with the values of a type pool
+type Visitor = Box<dyn FnMut(&mut dyn TypePoolTrait)>;
+
+/// Visitors per type called by [`TypePools::visit_all_mut`]
+pub struct VisitorRegistry {
+    visitors: HashMap<TypeId, Visitor>,
+}
+
+impl Default for VisitorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VisitorRegistry {
+    pub fn new() -> Self {
+        VisitorRegistry { visitors: HashMap::new() }
+    }
+
+    /// Register `f` to visit the values of `T`, replacing the previous visitor of `T`
+    pub fn add_visitor<T: 'static, F: FnMut(&mut [T]) + 'static>(&mut self, mut f: F) {
+        self.visitors.insert(TypeId::of::<T>(), Box::new(move |pool: &mut dyn TypePoolTrait| {
+            if let Some(deque) = TypeDeque::<T>::cast_mut(pool) {
+                f(deque.values.make_contiguous());
+                return;
+            }
+            let pool = TypePool::<T>::cast_mut(pool).expect("visitors are only called with the pool stored under their id");
+            f(pool.values.as_mut_slice());
+        }));
+    }
+}
+
+/// The values removed by [`TypePools::sweep_registered`]
+pub struct Swept {
+    /// `Vec<T>` of removed values for each type that was swept
+    removed: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl Swept {
+    /// Take the removed values of `T`. Returns an empty `Vec` if `T` wasn't swept or the values
+    /// were already taken
+    pub fn take<T: 'static>(&mut self) -> Vec<T> {
+        self.removed.remove(&TypeId::of::<T>())
+            .map(|removed| *unsafe { removed.downcast::<Vec<T>>().unwrap_unchecked() }) // safety: removed values are stored under the id of their type
+            .unwrap_or_default()
+    }
+}
+
+/// The types whose type pools can be created from their `TypeId` by
+/// [`TypePools::ensure_pool`]
+pub struct ConstructorRegistry {
+    constructors: HashMap<TypeId, fn() -> Box<dyn TypePoolTrait>>,
+}
+
+impl Default for ConstructorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConstructorRegistry {
+    pub fn new() -> Self {
+        ConstructorRegistry { constructors: HashMap::new() }
+    }
+
+    /// Register the constructor of the type pool of `T`
+    pub fn register<T: 'static>(&mut self) {
+        self.constructors.insert(TypeId::of::<T>(), || Box::new(TypePool::<T>::new()));
+    }
+}
+
+/// The [`memory_usage`](TypePools::memory_usage) before and after a
+/// [`compact`](TypePools::compact)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactReport {
+    pub before: usize,
+    pub after: usize,
+}
+
+impl CompactReport {
+    /// The amount of bytes that were released
+    pub fn reclaimed(&self) -> usize {
+        self.before - self.after
+    }
+}
+
+/// The memory used by [`TypePools`], returned by [`TypePools::memory_breakdown`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBreakdown {
+    /// Bytes holding values
+    pub used: usize,
+    /// Bytes allocated for values, but not holding any. This can be released by
+    /// [`compact`](TypePools::compact)
+    pub reserved: usize,
+    /// Estimated bytes of the map containing the type pools
+    pub map_overhead: usize,
+}
+
+/// A batch of pushes to a single type pool, created by [`TypePools::batch`]
+pub struct Batch<'a, T> {
+    target: BatchTarget<'a, T>,
+    /// The recorded events of the pools, `Some` if recording is enabled
+    events: Option<&'a mut Vec<PoolEvent>>,
+    /// The count of empty type pools of the pools
+    empty_types: &'a mut Option<usize>,
+}
+
+/// The values a [`Batch`] pushes to, depending on whether the type is in deque mode
+enum BatchTarget<'a, T> {
+    Pool(&'a mut TypePool<T>),
+    Deque(&'a mut VecDeque<T>),
+}
+
+impl<'a, T: 'static> Batch<'a, T> {
+    /// Add a value to the type pool
+    pub fn push(&mut self, value: T) {
+        let index = match &mut self.target {
+            BatchTarget::Pool(pool) => {
+                pool.values.push(value);
+                pool.update_peak();
+                pool.values.len() - 1
+            }
+            BatchTarget::Deque(values) => {
+                values.push_back(value);
+                values.len() - 1
+            }
+        };
+        if let (0, Some(count)) = (index, &mut self.empty_types) {
+            *count -= 1;
+        }
+        if let Some(events) = &mut self.events {
+            events.push(PoolEvent { type_id: TypeId::of::<T>(), kind: PoolEventKind::Push, index });
+        }
+    }
+}
+
+/// Iterator over the values of a type pool or the deque of a type in deque mode
+enum ValuesIter<'a, T> {
+    Pool(std::slice::Iter<'a, T>),
+    Deque(std::collections::vec_deque::Iter<'a, T>),
+}
+
+impl<'a, T> Iterator for ValuesIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        match self {
+            ValuesIter::Pool(iter) => iter.next(),
+            ValuesIter::Deque(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            ValuesIter::Pool(iter) => iter.size_hint(),
+            ValuesIter::Deque(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for ValuesIter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        match self {
+            ValuesIter::Pool(iter) => iter.next_back(),
+            ValuesIter::Deque(iter) => iter.next_back(),
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for ValuesIter<'a, T> {}
+
+type Callback<T> = Box<dyn FnMut(&T)>;
+
+/// Callbacks registered for a single type
+struct Observers<T> {
+    on_push: Vec<Callback<T>>,
+    on_remove: Vec<Callback<T>>,
+    on_empty: Vec<Box<dyn FnMut()>>,
+}
+
+impl<T> Observers<T> {
+    fn new() -> Self {
+        Self { on_push: Vec::new(), on_remove: Vec::new(), on_empty: Vec::new() }
+    }
+}
+
+mod sealed {
+    /// Also holds the type erased operations that are internal to the crate
+    pub trait Sealed {
+        fn last_touch(&self) -> u64;
+        /// The largest length seen, `None` for deques which don't keep track of it
+        fn peak_len(&self) -> Option<usize>;
+        fn update_peak(&mut self);
+        fn drain_into(&mut self, sink: &mut super::TypePools);
+    }
+}
+
+/// A type erased [`TypePool`]
+///
+/// This trait is sealed: it is only implemented by `TypePool<T>` and the deques of types in
+/// deque mode, see [`TypePools::make_deque`].
+pub trait TypePoolTrait: sealed::Sealed {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn is_empty(&self) -> bool;
+    /// The amount of values
+    fn len(&self) -> usize;
+    /// The amount of values that fit without reallocating
+    fn capacity(&self) -> usize;
+    /// The name of the type of the values, see [`std::any::type_name`]
+    fn type_name(&self) -> &'static str;
+    /// Reserve capacity for at least `additional` more values
+    fn reserve(&mut self, additional: usize);
+    /// Remove all values
+    fn clear(&mut self);
+    /// Keep only the first `len` values
+    fn truncate(&mut self, len: usize);
+    /// Shrink the capacity to fit the values
+    fn shrink_to_fit(&mut self);
+    /// The amount of bytes allocated for the values
+    fn memory_usage(&self) -> usize;
+    /// The `TypeId` of the values stored in this pool
+    fn value_type_id(&self) -> TypeId;
+    /// The size of a single value in bytes, see [`std::mem::size_of`]
+    fn element_size(&self) -> usize;
+    /// Create an empty type pool of the same type with at least the same capacity
+    fn clone_empty_with_capacity(&self) -> Box<dyn TypePoolTrait>;
+}
+
+#[derive(Clone)]
+pub struct TypePool<T> {
+    pub values: Vec<T>,
+    /// Value of `TypePools::touch_clock` at the last push
+    last_touch: u64,
+    /// The largest length seen by `TypePools` and by `clear` and `truncate`, see
+    /// [`TypePools::reserve_to_peak`]
+    peak_len: usize,
+}
+
+impl<T> Default for TypePool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<Vec<T>> for TypePool<T> {
+    fn from(values: Vec<T>) -> Self {
+        let peak_len = values.len();
+        Self { values, last_touch: 0, peak_len }
+    }
+}
+
+impl<T> TypePool<T> {
+    /// Create an empty type pool
+    pub fn new() -> Self {
+        Self::from(Vec::new())
+    }
+
+    /// Retain only the values for which `pred` returns `true`
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, pred: F) {
+        self.values.retain(pred);
+    }
+
+    /// Sort the values
+    pub fn sort(&mut self)
+    where
+        T: Ord,
+    {
+        self.values.sort();
+    }
+
+    /// Remove consecutive repeated values
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.values.dedup();
+    }
+
+    /// Remove all values
+    pub fn clear(&mut self) {
+        self.update_peak();
+        self.values.clear();
+    }
+
+    /// Keep only the first `len` values
+    pub fn truncate(&mut self, len: usize) {
+        self.update_peak();
+        self.values.truncate(len);
+    }
+
+    /// Record the current length if it is the largest one seen
+    fn update_peak(&mut self) {
+        self.peak_len = self.peak_len.max(self.values.len());
+    }
+
+    /// Bookkeeping after values were pushed at `touch` of `TypePools::touch_clock`
+    fn touch(&mut self, touch: u64) {
+        self.last_touch = touch;
+        self.update_peak();
+    }
+}
+
+impl<T: 'static> TypePool<T> {
+    fn cast(pool: &dyn TypePoolTrait) -> Option<&Self> {
+        pool.as_any()
+            .downcast_ref::<TypePool<T>>()
+    }
+
+    fn cast_mut(pool: &mut dyn TypePoolTrait) -> Option<&mut Self> {
+        pool.as_any_mut()
+            .downcast_mut::<TypePool<T>>()
+    }
+}
+
+impl<T: 'static> sealed::Sealed for TypePool<T> {
+    fn last_touch(&self) -> u64 {
+        self.last_touch
+    }
+
+    fn peak_len(&self) -> Option<usize> {
+        Some(self.peak_len)
+    }
+
+    fn update_peak(&mut self) {
+        TypePool::update_peak(self);
+    }
+
+    fn drain_into(&mut self, sink: &mut TypePools) {
+        let was_empty = sink.is_type_empty::<T>();
+        match sink.deque_values_mut::<T>() {
+            Some(deque) => deque.extend(self.values.drain(..)),
+            None => {
+                let pool = sink.pool_or_create::<T>();
+                pool.values.append(&mut self.values);
+                pool.update_peak();
+            }
+        }
+        sink.empty_changed(was_empty, sink.is_type_empty::<T>());
+    }
+}
+
+impl<T: 'static> TypePoolTrait for TypePool<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.values.capacity()
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.values.reserve(additional);
+    }
+
+    fn clear(&mut self) {
+        TypePool::clear(self);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        TypePool::truncate(self, len);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.values.shrink_to_fit();
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.values.capacity() * std::mem::size_of::<T>()
+    }
+
+    fn value_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn element_size(&self) -> usize {
+        std::mem::size_of::<T>()
+    }
+
+    fn clone_empty_with_capacity(&self) -> Box<dyn TypePoolTrait> {
+        let mut pool = TypePool::<T>::new();
+        pool.values.reserve_exact(self.values.capacity());
+        Box::new(pool)
+    }
+}
+
+/// The type pool of a type in deque mode, see [`TypePools::make_deque`]
+struct TypeDeque<T> {
+    values: VecDeque<T>,
+    /// Value of `TypePools::touch_clock` at the last push
+    last_touch: u64,
+}
+
+impl<T: 'static> TypeDeque<T> {
+    fn cast(pool: &dyn TypePoolTrait) -> Option<&Self> {
+        pool.as_any()
+            .downcast_ref::<TypeDeque<T>>()
+    }
+
+    fn cast_mut(pool: &mut dyn TypePoolTrait) -> Option<&mut Self> {
+        pool.as_any_mut()
+            .downcast_mut::<TypeDeque<T>>()
+    }
+}
+
+impl<T: 'static> sealed::Sealed for TypeDeque<T> {
+    fn last_touch(&self) -> u64 {
+        self.last_touch
+    }
+
+    fn peak_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn update_peak(&mut self) {}
+
+    fn drain_into(&mut self, sink: &mut TypePools) {
+        if !sink.contains_type::<T>() {
+            sink.make_deque::<T>();
+        }
+        let was_empty = sink.is_type_empty::<T>();
+        match sink.deque_values_mut::<T>() {
+            Some(deque) => deque.append(&mut self.values),
+            None => {
+                let pool = sink.pool_or_create::<T>();
+                pool.values.extend(self.values.drain(..));
+                pool.update_peak();
+            }
+        }
+        sink.empty_changed(was_empty, sink.is_type_empty::<T>());
+    }
+}
+
+impl<T: 'static> TypePoolTrait for TypeDeque<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.values.capacity()
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.values.reserve(additional);
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.values.truncate(len);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.values.shrink_to_fit();
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.values.capacity() * std::mem::size_of::<T>()
+    }
+
+    fn value_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn element_size(&self) -> usize {
+        std::mem::size_of::<T>()
+    }
+
+    fn clone_empty_with_capacity(&self) -> Box<dyn TypePoolTrait> {
+        Box::new(TypeDeque::<T> { values: VecDeque::with_capacity(self.values.capacity()), last_touch: 0 })
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use std::{any::TypeId, cell::Cell, rc::Rc};
+
+    use crate::{CloneRegistry, ConstructorRegistry, GrowthPolicy, Keep, PoolError, PoolEvent, PoolEventKind, PoolMetrics, SweepRegistry, TypePool, TypePools, VisitorRegistry};
+
+    #[test]
+    fn test_add() {
+        let mut pools = TypePools::new();
+        pools.push(1 as u32);
+        pools.push(2 as u32);
+        pools.push("Hello");
+        pools.push("World");
+
+        assert_eq!(*pools.get::<u32>(0).unwrap(), 1);
+        assert_eq!(*pools.get::<u32>(1).unwrap(), 2);
+        assert_eq!(*pools.get::<&str>(0).unwrap(), "Hello");
+        assert_eq!(*pools.get::<&str>(1).unwrap(), "World");
+    }
+
+    #[test]
+    fn test_example() {
+        // Create a Type Pools structure
+        let mut pools = TypePools::new();
+
+        // Adding values
+        pools.push(1 as u32);
+        pools.push(2 as u32);
+        pools.push("Hello world");
+
+        // Query values
+        let int_pool = pools.type_pool::<u32>().unwrap();
+        let int_value: u32 = int_pool.values[0];
+        let string_value: &str = pools.get::<&str>(0).unwrap();
+
+        assert_eq!(int_value, 1);
+        assert_eq!(string_value, "Hello world");
+    }
+
+    #[test]
+    #[cfg(feature = "bytemuck")]
+    fn test_bytes_round_trip() {
+        let pools = TypePools::from_pool(vec![[1u32, 2, 3], [4, 5, 6]]);
+        let bytes = pools.as_bytes::<[u32; 3]>().unwrap().to_vec();
+        assert_eq!(bytes.len(), 24);
+
+        let mut loaded = TypePools::new();
+        loaded.from_bytes::<[u32; 3]>(&bytes).unwrap();
+        loaded.from_bytes::<[u32; 3]>(&bytes[1..13]).unwrap();
+        assert_eq!(loaded.type_pool::<[u32; 3]>().unwrap().values[..2], pools.type_pool::<[u32; 3]>().unwrap().values[..]);
+        assert_eq!(loaded.len::<[u32; 3]>(), Some(3));
+        assert!(loaded.from_bytes::<[u32; 3]>(&bytes[..5]).is_err());
+        assert_eq!(pools.as_bytes::<u64>(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serialize_pool() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3]);
+        let data = pools.serialize_pool::<u32>().unwrap().unwrap();
+        assert!(pools.serialize_pool::<u64>().unwrap().is_none());
+
+        let mut loaded = TypePools::new();
+        loaded.deserialize_pool::<u32>(&data).unwrap();
+        assert_eq!(loaded.type_pool::<u32>().unwrap().values, vec![1, 2, 3]);
+        assert!(loaded.deserialize_pool::<u32>(b"[4, -1]").is_err());
+        assert_eq!(loaded.type_pool::<u32>().unwrap().values, vec![1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_shuffle() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let shuffled = |seed| {
+            let mut pools = TypePools::from_pool((0..20u32).collect());
+            pools.shuffle::<u32, _>(&mut StdRng::seed_from_u64(seed)).unwrap();
+            pools.into_values::<u32>()
+        };
+        let values = shuffled(7);
+        assert_eq!(values, shuffled(7));
+        assert_ne!(values, (0..20).collect::<Vec<u32>>());
+        let mut sorted = values.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<u32>>());
+
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3]);
+        let mut rng = StdRng::seed_from_u64(7);
+        assert!(pools.choose::<u32, _>(&mut rng).is_some_and(|v| (1..=3).contains(v)));
+        assert_eq!(pools.shuffle::<u64, _>(&mut rng), None);
+        assert_eq!(pools.choose::<u64, _>(&mut rng), None);
+    }
+
+    #[test]
+    #[cfg(feature = "rand")]
+    fn test_sample() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let pools = TypePools::from_pool((0..20u32).collect());
+        let sample = pools.sample::<u32, _>(&mut StdRng::seed_from_u64(3), 5).unwrap();
+        assert_eq!(sample, pools.sample::<u32, _>(&mut StdRng::seed_from_u64(3), 5).unwrap());
+        assert_eq!(sample.len(), 5);
+        let mut distinct = sample.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 5);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(pools.sample::<u32, _>(&mut rng, 30).unwrap().len(), 20);
+        assert_eq!(pools.sample::<u64, _>(&mut rng, 1), None);
+    }
+
+    #[test]
+    fn test_index_of() {
+        let mut pools = TypePools::new();
+        for v in [1u32, 2, 1, 3] {
+            pools.push(v);
+        }
+
+        assert_eq!(pools.first_index_of(&1u32), Some(0));
+        assert_eq!(pools.last_index_of(&1u32), Some(2));
+        assert_eq!(pools.first_index_of(&4u32), None);
+        assert_eq!(pools.last_index_of(&1u64), None);
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut pools = TypePools::new();
+        for v in [1u32, 2, 3] {
+            pools.push(v);
+        }
+
+        assert_eq!(pools.apply::<u32, _, _>(|s| s.reverse()), Some(()));
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![3, 2, 1]);
+        assert_eq!(pools.apply::<u64, _, _>(|s| s.len()), None);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut pools = TypePools::new();
+        pools.push(1u32);
+        pools.push(2u32);
+
+        assert_eq!(pools.remove::<u32>(2), None);
+        assert_eq!(pools.remove::<u32>(0), Some(1));
+        assert_eq!(pools.get::<u32>(0), Some(&2));
+        assert_eq!(pools.remove::<u64>(0), None);
+    }
+
+    #[test]
+    fn test_observers() {
+        let mut pools = TypePools::new();
+        let pushed = Rc::new(Cell::new(0));
+        let removed = Rc::new(Cell::new(0));
+        let counter = pushed.clone();
+        pools.on_push::<u32, _>(move |_| counter.set(counter.get() + 1));
+        let counter = removed.clone();
+        pools.on_remove::<u32, _>(move |_| counter.set(counter.get() + 1));
+
+        pools.push(1u32);
+        pools.push(2u32);
+        pools.push(3u32);
+        pools.push("not observed");
+        assert_eq!(pushed.get(), 3);
+
+        pools.pop::<u32>();
+        pools.remove::<u32>(0);
+        pools.remove::<u32>(5);
+        assert_eq!(removed.get(), 2);
+    }
+
+    #[test]
+    fn test_batch_touches_pool() {
+        let mut pools = TypePools::new();
+        pools.set_max_types(2);
+        pools.push(1u32);
+        pools.push(1u64);
+        pools.batch::<u32>(1).push(2);
+        pools.push(1u8);
+
+        assert!(pools.contains_type::<u32>());
+        assert!(!pools.contains_type::<u64>());
+    }
+
+    #[test]
+    fn test_batch() {
+        let mut batched = TypePools::new();
+        let mut batch = batched.batch::<u32>(10_000);
+        for i in 0..10_000u32 {
+            batch.push(i);
+        }
+
+        let mut pushed = TypePools::new();
+        for i in 0..10_000u32 {
+            pushed.push(i);
+        }
+
+        assert_eq!(batched.len::<u32>(), Some(10_000));
+        assert_eq!(batched.len::<u32>(), pushed.len::<u32>());
+        assert_eq!(batched.get::<u32>(9_999), Some(&9_999));
+    }
+
+    #[test]
+    fn test_inner_round_trip() {
+        let mut pools = TypePools::new();
+        pools.push(1u32);
+        pools.push("Hello");
+
+        let inner = pools.into_inner();
+        assert_eq!(inner.len(), 2);
+        let pools = TypePools::from_inner(inner);
+
+        assert_eq!(pools.get::<u32>(0), Some(&1));
+        assert_eq!(pools.get::<&str>(0), Some(&"Hello"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_inner_wrong_id() {
+        let mut pools = TypePools::new();
+        pools.push(1u32);
+
+        let mut inner = pools.into_inner();
+        let pool = inner.remove(&TypeId::of::<u32>()).unwrap();
+        inner.insert(TypeId::of::<u64>(), pool);
+        TypePools::from_inner(inner);
+    }
+
+    #[test]
+    fn test_max_types() {
+        let mut pools = TypePools::new();
+        pools.set_max_types(2);
+        pools.push(1u32);
+        pools.push(2u64);
+        pools.push(3u32);
+        pools.push("Hello");
+
+        assert_eq!(pools.types_count(), 2);
+        assert_eq!(pools.len::<u64>(), None);
+        assert_eq!(pools.len::<u32>(), Some(2));
+        assert_eq!(pools.len::<&str>(), Some(1));
+
+        pools.pop::<u32>();
+        pools.pop::<u32>();
+        pools.push(4u8);
+        assert_eq!(pools.len::<u32>(), None);
+        assert_eq!(pools.len::<&str>(), Some(1));
+    }
+
+    #[test]
+    fn test_type_pool_or_default() {
+        let mut pools = TypePools::new();
+        assert_eq!(pools.type_pool_or_default::<u32>().values.len(), 0);
+        assert_eq!(pools.len::<u32>(), Some(0));
+
+        pools.push(1u32);
+        assert_eq!(pools.type_pool_or_default::<u32>().values, vec![1]);
+    }
+
+    #[test]
+    fn test_or_zero() {
+        let mut pools = TypePools::new();
+        pools.batch::<u32>(10).push(1);
+
+        assert_eq!(pools.len_or_zero::<u64>(), 0);
+        assert_eq!(pools.capacity_or_zero::<u64>(), 0);
+        assert_eq!(pools.len_or_zero::<u32>(), 1);
+        assert!(pools.capacity_or_zero::<u32>() >= 10);
+    }
+
+    #[test]
+    fn test_retain_with_index() {
+        let mut pools = TypePools::new();
+        for v in ["a", "b", "c", "d"] {
+            pools.push(v);
+        }
+
+        pools.retain_with_index::<&str, _>(|idx, _| idx % 2 == 0);
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["a", "c"]);
+    }
+
+    #[test]
+    fn test_swap_remove_value() {
+        let mut pools = TypePools::new();
+        for v in [10u32, 20, 30] {
+            pools.push(v);
+        }
+
+        assert_eq!(pools.swap_remove_value(&20u32), Some(20));
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![10, 30]);
+        assert_eq!(pools.swap_remove_value(&20u32), None);
+        assert_eq!(pools.swap_remove_value(&20u64), None);
+    }
+
+    #[test]
+    fn test_drain_into() {
+        let mut source = TypePools::new();
+        source.push(1u32);
+        source.push(2u32);
+        source.push(String::from("Hello"));
+        let mut sink = TypePools::new();
+        sink.push(0u32);
+
+        source.drain_into(&mut sink);
+
+        assert_eq!(source.len::<u32>(), Some(0));
+        assert_eq!(source.len::<String>(), Some(0));
+        assert_eq!(sink.type_pool::<u32>().unwrap().values, vec![0, 1, 2]);
+        assert_eq!(sink.get::<String>(0).map(String::as_str), Some("Hello"));
+    }
+
+    #[test]
+    fn test_map_in_place() {
+        let mut pools = TypePools::new();
+        for v in [0u32, 1, 2] {
+            pools.push(v);
+        }
+
+        assert_eq!(pools.map_in_place::<u32, _>(|v| *v += 1), Some(()));
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 2, 3]);
+        assert_eq!(pools.map_in_place::<u64, _>(|v| *v += 1), None);
+    }
+
+    #[test]
+    fn test_macro() {
+        let pools = crate::type_pools! {
+            u32 => [1, 2, 3],
+            &str => ["a", "b"],
+        };
+
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 2, 3]);
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["a", "b"]);
+        assert_eq!(crate::type_pools! {}.types_count(), 0);
+    }
+
+    #[test]
+    fn test_iter_mut_with_ref() {
+        let mut pools = TypePools::new();
+        pools.extend([1u32, 2, 3]);
+        pools.extend([0.5f32, 1.5]);
+
+        let (ints, floats) = pools.iter_mut_with_ref::<u32, f32>().unwrap();
+        for int in ints {
+            *int += floats.values.iter().sum::<f32>() as u32;
+        }
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![3, 4, 5]);
+
+        assert!(pools.iter_mut_with_ref::<u32, u32>().is_none());
+        assert!(pools.iter_mut_with_ref::<u32, u64>().is_none());
+    }
+
+    #[test]
+    fn test_reserve_all() {
+        let mut pools = TypePools::new();
+        pools.extend([1u32, 2]);
+        pools.extend(["a"]);
+
+        pools.reserve_all(100);
+        assert!(pools.capacity_or_zero::<u32>() >= 102);
+        assert!(pools.capacity_or_zero::<&str>() >= 101);
+    }
+
+    #[test]
+    fn test_is_type_empty() {
+        let mut pools = TypePools::new();
+        pools.type_pool_or_default::<u32>();
+        pools.push("Hello");
+
+        assert!(pools.is_type_empty::<u64>());
+        assert!(!pools.has_nonempty_type::<u64>());
+        assert!(pools.is_type_empty::<u32>());
+        assert!(!pools.has_nonempty_type::<u32>());
+        assert!(!pools.is_type_empty::<&str>());
+        assert!(pools.has_nonempty_type::<&str>());
+    }
+
+    #[test]
+    fn test_pool_data_ptr() {
+        let mut pools = TypePools::new();
+        pools.batch::<u32>(8).push(1);
+        let ptr = pools.pool_data_ptr::<u32>();
+
+        pools.type_pool_mut::<u32>().unwrap().values.clear();
+        pools.extend([1u32, 2, 3, 4]);
+        assert_eq!(pools.pool_data_ptr::<u32>(), ptr);
+        assert_eq!(pools.pool_data_ptr::<u64>(), None);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut pools = TypePools::new();
+        pools.extend(0..100u32);
+        pools.extend(0..10u64);
+        pools.type_pool_mut::<u32>().unwrap().values.truncate(10);
+        pools.type_pool_mut::<u64>().unwrap().values.clear();
+        let before = pools.memory_usage();
+
+        let report = pools.compact();
+        assert_eq!(report.before, before);
+        assert_eq!(report.after, pools.memory_usage());
+        assert!(pools.memory_usage() < before);
+        assert!(report.reclaimed() > 0);
+        assert_eq!(pools.types_count(), 1);
+    }
+
+    #[test]
+    fn test_from_pool() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3])
+            .with_pool(vec!["a"])
+            .with_pool(vec![4u32]);
+
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 2, 3, 4]);
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["a"]);
+    }
+
+    #[test]
+    fn test_get3_mut() {
+        let mut pools = TypePools::new();
+        pools.push(1u32);
+        pools.push(1.0f32);
+        pools.push(String::from("a"));
+
+        let (ints, floats, strings) = pools.get3_mut::<u32, f32, String>();
+        let (ints, floats, strings) = (ints.unwrap(), floats.unwrap(), strings.unwrap());
+        ints.values[0] += 1;
+        floats.values[0] *= 2.0;
+        strings.values[0].push('b');
+
+        assert_eq!(pools.get::<u32>(0), Some(&2));
+        assert_eq!(pools.get::<f32>(0), Some(&2.0));
+        assert_eq!(pools.get::<String>(0).map(String::as_str), Some("ab"));
+        assert!(pools.get3_mut::<u32, f32, u64>().2.is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_get3_mut_same_type() {
+        let mut pools = TypePools::new();
+        pools.push(1u32);
+        let _ = pools.get3_mut::<u32, f32, u32>();
+    }
+
+    #[test]
+    fn test_pop_front() {
+        let mut pools = TypePools::new();
+        pools.extend([1u32, 2, 3]);
+
+        assert_eq!(pools.pop_front::<u32>(), Some(1));
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![2, 3]);
+        assert_eq!(pools.pop_front::<u64>(), None);
+    }
+
+    #[test]
+    fn test_deque() {
+        let mut pools = TypePools::new();
+        pools.push(2u32);
+        pools.make_deque::<u32>();
+        pools.push_back(3u32);
+        pools.push_front(1u32);
+        pools.push(4u32);
+
+        assert_eq!(pools.type_deque::<u32>().unwrap(), &[1, 2, 3, 4]);
+        assert_eq!(pools.type_pool::<u32>().map(|p| p.values.len()), None);
+        assert_eq!(pools.pop_front::<u32>(), Some(1));
+        assert_eq!(pools.pop_back::<u32>(), Some(4));
+        assert_eq!(pools.type_deque::<u32>().unwrap(), &[2, 3]);
+
+        pools.push_front(1u64);
+        pools.push_front(0u64);
+        assert_eq!(pools.type_pool::<u64>().unwrap().values, vec![0, 1]);
+        assert_eq!(pools.pop_back::<u64>(), Some(1));
+    }
+
+    #[test]
+    fn test_deque_is_a_type_pool() {
+        let mut pools = TypePools::new();
+        pools.make_deque::<u32>();
+        pools.push(1u32);
+        pools.extend([2u32, 3]);
+        pools.push(4u32);
+        pools.push_all(vec![5u32]);
+
+        assert_eq!(pools.type_deque::<u32>().unwrap(), &[1, 2, 3, 4, 5]);
+        assert_eq!(pools.len::<u32>(), Some(5));
+        assert_eq!(pools.types_count(), 1);
+        assert_eq!(pools.total_len(), 5);
+        assert_eq!(pools.push_default::<u32>(), 5);
+        *pools.get_mut_or_insert(6, 0u32).unwrap() = 7;
+        assert_eq!(pools.get::<u32>(6), Some(&7));
+
+        let mut sink = TypePools::new();
+        pools.drain_into(&mut sink);
+        assert_eq!(sink.type_deque::<u32>().unwrap(), &[1, 2, 3, 4, 5, 0, 7]);
+        assert_eq!(sink.clone_shape().types_count(), 1);
+        assert_eq!(sink.into_values::<u32>(), vec![1, 2, 3, 4, 5, 0, 7]);
+    }
+
+    #[test]
+    fn test_clone_registered_into() {
+        let mut registry = CloneRegistry::new();
+        registry.register::<u32>();
+        let mut pools = TypePools::new();
+        pools.extend([1u32, 2]);
+        pools.push(String::from("Hello"));
+        let mut dst = TypePools::new();
+        dst.push(0u32);
+
+        let skipped = pools.clone_registered_into(&registry, &mut dst);
+        assert_eq!(skipped, vec![TypeId::of::<String>()]);
+        assert_eq!(dst.type_pool::<u32>().unwrap().values, vec![0, 1, 2]);
+        assert_eq!(dst.len::<String>(), None);
+        assert_eq!(pools.len::<u32>(), Some(2));
+    }
+
+    #[test]
+    fn test_entry_or_insert_with() {
+        let mut pools = TypePools::new();
+        assert_eq!(pools.entry_or_insert_with(|| vec![1u32, 2, 3]).values, vec![1, 2, 3]);
+        assert_eq!(pools.entry_or_insert_with::<u32, _>(|| unreachable!()).values, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_checked_get() {
+        let mut pools = TypePools::new();
+        pools.extend([1u32, 2]);
+
+        assert_eq!(pools.checked_get::<u32>(1), Ok(&2));
+        assert_eq!(pools.checked_get::<u64>(0), Err(PoolError::TypeNotFound));
+        assert_eq!(pools.checked_get::<u32>(2), Err(PoolError::IndexOutOfBounds { idx: 2, len: 2 }));
+
+        *pools.checked_get_mut::<u32>(0).unwrap() = 3;
+        assert_eq!(pools.get::<u32>(0), Some(&3));
+        assert_eq!(pools.checked_get_mut::<u32>(5), Err(PoolError::IndexOutOfBounds { idx: 5, len: 2 }));
+    }
+
+    #[test]
+    fn test_transfer() {
+        let mut src = TypePools::new();
+        src.extend([1u32, 2]);
+        let mut dst = TypePools::new();
+
+        assert_eq!(src.transfer::<u32>(0, &mut dst), Some(()));
+        assert_eq!(src.type_pool::<u32>().unwrap().values, vec![2]);
+        assert_eq!(dst.type_pool::<u32>().unwrap().values, vec![1]);
+        assert_eq!(src.transfer::<u32>(1, &mut dst), None);
+        assert_eq!(src.transfer::<u64>(0, &mut dst), None);
+    }
+
+    #[test]
+    fn test_drain_iter() {
+        let mut pools = TypePools::new();
+        pools.extend([1u32, 2, 3, 4]);
+
+        let drained: Vec<u32> = pools.drain_iter::<u32>().unwrap().take(2).collect();
+        assert_eq!(drained, vec![1, 2]);
+        assert_eq!(pools.len::<u32>(), Some(0));
+        assert!(pools.drain_iter::<u64>().is_none());
+    }
+
+    #[test]
+    fn test_diff_pool() {
+        let a = TypePools::from_pool(vec![1u32, 2, 3]);
+        let b = TypePools::from_pool(vec![1u32, 9, 3, 4]);
+
+        assert_eq!(a.diff_pool::<u32>(&b), Some(vec![1, 3]));
+        assert_eq!(a.diff_pool::<u32>(&a), Some(vec![]));
+        assert_eq!(a.diff_pool::<u32>(&TypePools::new()), Some(vec![0, 1, 2]));
+        assert_eq!(a.diff_pool::<u64>(&b), None);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-tracking")]
+    fn test_active_borrows() {
+        let mut pools = TypePools::new();
+        pools.extend([1u32, 2]);
+        pools.push(1u64);
+
+        {
+            let ints = pools.tracked_type_pool::<u32>().unwrap();
+            let longs = pools.tracked_type_pool::<u64>().unwrap();
+            assert_eq!(ints.values.len() + longs.values.len(), 3);
+            assert_eq!(pools.active_borrows(), 2);
+        }
+        assert_eq!(pools.active_borrows(), 0);
+
+        pools.tracked_type_pool_mut::<u32>().unwrap().values.push(3);
+        assert_eq!(pools.active_borrows(), 0);
+        assert_eq!(pools.len::<u32>(), Some(3));
+    }
+
+    #[test]
+    fn test_init_defaults() {
+        let mut pools = TypePools::new();
+        pools.extend([1u32, 2, 3, 4, 5, 6]);
+
+        pools.init_defaults::<u32>(4);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![0; 4]);
+        pools.init_defaults::<String>(2);
+        assert_eq!(pools.type_pool::<String>().unwrap().values, vec![String::new(); 2]);
+    }
+
+    #[test]
+    fn test_project() {
+        let pools = TypePools::from_pool(vec![1u32, 2]);
+
+        let projected = pools.project::<u32, String, _>(|v| v.to_string());
+        assert_eq!(projected.type_pool::<String>().unwrap().values, vec!["1", "2"]);
+        assert_eq!(projected.types_count(), 1);
+        assert_eq!(pools.len::<u32>(), Some(2));
+        assert_eq!(pools.project::<u64, String, _>(|v| v.to_string()).types_count(), 0);
+    }
+
+    #[test]
+    fn test_get_two() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3]);
+
+        let (a, b) = pools.get_two::<u32>(0, 2).unwrap();
+        assert!(a < b);
+        assert_eq!(pools.get_two::<u32>(1, 1), Some((&2, &2)));
+        assert_eq!(pools.get_two::<u32>(0, 3), None);
+        assert_eq!(pools.get_two::<u64>(0, 0), None);
+    }
+
+    #[test]
+    fn test_reserve_exact_to() {
+        let mut pools = TypePools::new();
+        pools.reserve_exact_to::<u32>(100);
+        assert_eq!(pools.capacity_or_zero::<u32>(), 100);
+
+        pools.reserve_exact_to::<u32>(50);
+        assert_eq!(pools.capacity_or_zero::<u32>(), 100);
+    }
+
+    #[test]
+    fn test_drain_sorted() {
+        let mut pools = TypePools::from_pool(vec![3u32, 1, 2]);
+
+        assert_eq!(pools.drain_sorted::<u32>(), Some(vec![1, 2, 3]));
+        assert_eq!(pools.len::<u32>(), Some(0));
+        assert_eq!(pools.drain_sorted::<u64>(), None);
+    }
+
+    #[test]
+    fn test_iter_types() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]);
+        pools.push("Hello");
+
+        let mut types: Vec<(TypeId, usize)> = pools.iter_types().collect();
+        let mut expected: Vec<(TypeId, usize)> = pools.types().into_iter()
+            .map(|id| (*id, if *id == TypeId::of::<u32>() { pools.len_or_zero::<u32>() } else { pools.len_or_zero::<&str>() }))
+            .collect();
+        types.sort_by_key(|(_, len)| *len);
+        expected.sort_by_key(|(_, len)| *len);
+        assert_eq!(types, expected);
+        assert_eq!(types, vec![(TypeId::of::<&str>(), 1), (TypeId::of::<u32>(), 2)]);
+    }
+
+    #[test]
+    fn test_shrink_if_oversized() {
+        let mut pools = TypePools::new();
+        pools.reserve_exact_to::<u32>(100);
+        pools.extend(0..10u32);
+
+        assert_eq!(pools.shrink_if_oversized::<u32>(20.0), Some(false));
+        assert_eq!(pools.shrink_if_oversized::<u32>(4.0), Some(true));
+        assert!(pools.capacity_or_zero::<u32>() < 100);
+        assert_eq!(pools.shrink_if_oversized::<u64>(4.0), None);
+    }
+
+    #[test]
+    fn test_contains_all_any() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3]);
+
+        assert!(pools.contains_all(&[1u32, 3]));
+        assert!(!pools.contains_all(&[1u32, 9]));
+        assert!(pools.contains_any(&[1u32, 9]));
+        assert!(!pools.contains_any(&[8u32, 9]));
+        assert!(pools.contains_all::<u64>(&[]));
+        assert!(!pools.contains_all(&[1u64]));
+        assert!(!pools.contains_any(&[1u64]));
+    }
+
+    #[test]
+    fn test_view() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]);
+        pools.push("Hello");
+        let view = pools.as_view();
+
+        assert_eq!(view.get::<u32>(0), Some(&1));
+        assert_eq!(view.iter::<u32>().unwrap().sum::<u32>(), 3);
+        assert_eq!(view.len::<&str>(), Some(1));
+        assert_eq!(view.type_pool::<u64>().map(|p| p.values.len()), None);
+        assert_eq!(view.types_count(), 2);
+        assert_eq!(view.types().len(), 2);
+    }
+
+    #[test]
+    fn test_auto_prune() {
+        let mut pools = TypePools::from_pool(vec![1u32])
+            .with_pool(vec![1u64, 2])
+            .with_pool(vec!["Hello"]);
+        pools.set_auto_prune(2);
+
+        pools.pop::<u32>();
+        assert_eq!(pools.types_count(), 3);
+        pools.remove::<u64>(0);
+        pools.remove::<u64>(0);
+        assert_eq!(pools.types_count(), 1);
+        assert_eq!(pools.len::<&str>(), Some(1));
+    }
+
+    #[test]
+    fn test_auto_prune_counts_empty_pools() {
+        let mut pools = TypePools::from_pool(vec![1u32])
+            .with_pool(vec![1u64]);
+        pools.set_auto_prune(2);
+
+        pools.pop::<u32>();
+        pools.push(2u32);
+        pools.pop::<u64>();
+        assert_eq!(pools.types_count(), 2);
+        pools.pop::<u32>();
+        assert_eq!(pools.types_count(), 0);
+    }
+
+    #[test]
+    fn test_push_if_absent_type() {
+        let mut pools = TypePools::new();
+
+        assert!(pools.push_if_absent_type(1u32));
+        assert!(!pools.push_if_absent_type(2u32));
+        assert_eq!(pools.len::<u32>(), Some(1));
+        assert!(pools.contains_type::<u32>());
+        assert!(!pools.contains_type::<u64>());
+    }
+
+    #[test]
+    fn test_single() {
+        let mut pools = TypePools::from_pool(vec![1u32]).with_pool(vec![1u64, 2]);
+
+        assert_eq!(pools.single::<u32>(), Some(&1));
+        assert_eq!(pools.single::<u64>(), None);
+        assert_eq!(pools.single::<u8>(), None);
+        *pools.single_mut::<u32>().unwrap() = 2;
+        assert_eq!(pools.get::<u32>(0), Some(&2));
+        assert_eq!(pools.single_mut::<u64>(), None);
+    }
+
+    #[test]
+    fn test_set_values() {
+        let mut pools = TypePools::new();
+
+        assert_eq!(pools.set_values(vec![1u32, 2]), None);
+        assert_eq!(pools.set_values(vec![3u32]), Some(vec![1, 2]));
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![3]);
+    }
+
+    #[test]
+    fn test_ensure_pool() {
+        let mut registry = ConstructorRegistry::new();
+        registry.register::<u32>();
+        let mut pools = TypePools::new();
+
+        assert!(pools.ensure_pool(TypeId::of::<u32>(), &registry));
+        assert_eq!(pools.len::<u32>(), Some(0));
+        pools.push(1u32);
+        assert!(pools.ensure_pool(TypeId::of::<u32>(), &registry));
+        assert_eq!(pools.get::<u32>(0), Some(&1));
+        assert!(!pools.ensure_pool(TypeId::of::<u64>(), &registry));
+        assert!(!pools.contains_type::<u64>());
+    }
+
+    #[test]
+    fn test_pool_as_any() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]);
+
+        let pool = pools.pool_as_any(TypeId::of::<u32>()).unwrap()
+            .downcast_ref::<TypePool<u32>>().unwrap();
+        assert_eq!(pool.values, vec![1, 2]);
+        pools.pool_as_any_mut(TypeId::of::<u32>()).unwrap()
+            .downcast_mut::<TypePool<u32>>().unwrap()
+            .values.push(3);
+        assert_eq!(pools.len::<u32>(), Some(3));
+        assert!(pools.pool_as_any(TypeId::of::<u64>()).is_none());
+    }
+
+    #[test]
+    fn test_swap_remove_many() {
+        let mut pools = TypePools::from_pool(vec!["a", "b", "c", "d"]);
+
+        assert_eq!(pools.swap_remove_many::<&str>(&[0, 2, 2, 7]), vec!["c", "a"]);
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["d", "b"]);
+        assert_eq!(pools.swap_remove_many::<u32>(&[0]), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_push_pair() {
+        let mut pools = TypePools::new();
+        for i in 0..3u32 {
+            assert_eq!(pools.push_pair(i, i.to_string()), i as usize);
+        }
+
+        for i in 0..3 {
+            assert_eq!(pools.get::<String>(i), Some(&pools.get::<u32>(i).unwrap().to_string()));
+        }
+    }
+
+    #[test]
+    fn test_indices() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3]);
+
+        assert_eq!(pools.indices::<u32>(), Some(0..3));
+        assert_eq!(pools.indices::<u64>(), None);
+    }
+
+    #[test]
+    fn test_merge_with() {
+        let mut pools = TypePools::from_pool(vec![3u32, 1, 2]);
+
+        pools.merge_with(vec![5u32, 4], |values, mut other| {
+            values.append(&mut other);
+            values.sort();
+        });
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_retain_count() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3, 4]);
+
+        assert_eq!(pools.retain_count::<u32, _>(|v| v % 2 == 0), 2);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![2, 4]);
+        assert_eq!(pools.retain_count::<u64, _>(|_| false), 0);
+    }
+
+    #[test]
+    fn test_with_capacity_for() {
+        let pools = TypePools::new()
+            .with_capacity_for::<u32>(1000)
+            .with_capacity_for::<String>(50);
+
+        assert!(pools.capacity_or_zero::<u32>() >= 1000);
+        assert!(pools.capacity_or_zero::<String>() >= 50);
+        assert_eq!(pools.len::<u32>(), Some(0));
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut pools = TypePools::from_pool(vec!["a", "b", "c"]);
+        pools.trim_front::<&str>(1);
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["b", "c"]);
+
+        let mut pools = TypePools::from_pool(vec!["a", "b", "c"]);
+        pools.trim_back::<&str>(1);
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["a", "b"]);
+        pools.trim_back::<&str>(5);
+        pools.trim_front::<u32>(5);
+        assert_eq!(pools.len::<&str>(), Some(0));
+    }
+
+    #[test]
+    fn test_extend_exact() {
+        let mut pools = TypePools::new();
+        pools.extend_exact((0..100u32).collect::<Vec<_>>());
+
+        assert_eq!(pools.len::<u32>(), Some(100));
+        assert_eq!(pools.capacity_or_zero::<u32>(), 100);
+    }
+
+    #[test]
+    fn test_copy_from_slice_reusing() {
+        let mut pools = TypePools::new();
+        pools.copy_from_slice_reusing(&[1u32, 2, 3, 4]);
+        let ptr = pools.pool_data_ptr::<u32>();
+
+        pools.copy_from_slice_reusing(&[5u32, 6]);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![5, 6]);
+        assert_eq!(pools.pool_data_ptr::<u32>(), ptr);
+    }
+
+    #[test]
+    fn test_is_sorted() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3]);
+        assert_eq!(pools.is_sorted::<u32>(), Some(true));
+
+        pools.set_values(vec![3u32, 1, 2]);
+        assert_eq!(pools.is_sorted::<u32>(), Some(false));
+        assert_eq!(pools.is_sorted::<u64>(), None);
+    }
+
+    #[test]
+    fn test_reverse_search() {
+        let pools = TypePools::from_pool(vec![2u32, 3, 4, 5]);
+
+        assert_eq!(pools.rposition::<u32, _>(|v| v % 2 == 0), Some(2));
+        assert_eq!(pools.rfind::<u32, _>(|v| *v % 2 == 1), Some(&5));
+        assert_eq!(pools.rposition::<u32, _>(|v| *v > 5), None);
+        assert_eq!(pools.rfind::<u64, _>(|_| true), None);
+    }
+
+    #[test]
+    fn test_split_at() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3, 4]);
+
+        assert_eq!(pools.split_at::<u32>(2), Some((&[1, 2][..], &[3, 4][..])));
+        let (left, right) = pools.split_at_mut::<u32>(2).unwrap();
+        left[0] = right[1];
+        assert_eq!(pools.get::<u32>(0), Some(&4));
+        assert_eq!(pools.split_at::<u64>(0), None);
+    }
+
+    #[test]
+    fn test_reduce() {
+        let mut pools = TypePools::from_pool(vec![3u32, 1, 4, 1, 5]);
+
+        assert_eq!(pools.reduce::<u32, _>(|a, b| *a.max(b)), Some(5));
+        assert_eq!(pools.reduce::<u32, _>(|a, b| a + b), Some(14));
+        pools.set_values(vec![7u32]);
+        assert_eq!(pools.reduce::<u32, _>(|a, b| a + b), Some(7));
+        pools.set_values(Vec::<u32>::new());
+        assert_eq!(pools.reduce::<u32, _>(|a, b| a + b), None);
+        assert_eq!(pools.reduce::<u64, _>(|a, b| a + b), None);
+    }
+
+    #[test]
+    fn test_push_all() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3]);
+
+        pools.push_all(vec![4u32, 5, 6]);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_type_pool_constructors() {
+        let pool = TypePool::from(vec![1u32, 2]);
+        assert_eq!(pool.values, vec![1, 2]);
+        assert!(TypePool::<u32>::new().values.is_empty());
+    }
+
+    #[test]
+    fn test_push_all_touches_pool() {
+        let mut pools = TypePools::new();
+        pools.set_max_types(2);
+        pools.push(1u32);
+        pools.push(1u64);
+        pools.push_all(vec![2u32]);
+        pools.push(1u8);
+
+        assert!(pools.contains_type::<u32>());
+        assert!(!pools.contains_type::<u64>());
+    }
+
+    #[test]
+    fn test_pool_index() {
+        let mut pools = TypePools::new();
+        let a = pools.push_indexed(1u32);
+        let b = pools.push_indexed(2u32);
+        let s = pools.push_indexed(String::from("Hello"));
+
+        assert_eq!(b.index(), 1);
+        assert_eq!(pools.get_indexed(a), Some(&1));
+        assert_eq!(pools.get_indexed(b), Some(&2));
+        assert_eq!(pools.get_indexed(s).map(String::as_str), Some("Hello"));
+    }
+
+    #[test]
+    fn test_clone_pool() {
+        let pools = TypePools::from_pool(vec![String::from("Hello")]);
+
+        let mut pool = pools.type_pool::<String>().unwrap().clone();
+        pool.values[0].push_str(" world");
+        assert_eq!(pool.values, vec!["Hello world"]);
+        assert_eq!(pools.get::<String>(0).map(String::as_str), Some("Hello"));
+    }
+
+    #[test]
+    fn test_maybe_shrink() {
+        fn push_types<const N: usize>(pools: &mut TypePools) {
+            pools.push([0u8; N]);
+            pools.push([0u16; N]);
+            pools.push([0u32; N]);
+            pools.push([0u64; N]);
+        }
+        let mut pools = TypePools::new();
+        push_types::<0>(&mut pools);
+        push_types::<1>(&mut pools);
+        push_types::<2>(&mut pools);
+        push_types::<3>(&mut pools);
+        push_types::<4>(&mut pools);
+        push_types::<5>(&mut pools);
+        push_types::<6>(&mut pools);
+        push_types::<7>(&mut pools);
+        push_types::<8>(&mut pools);
+        push_types::<9>(&mut pools);
+
+        pools.maybe_shrink();
+        let capacity = pools.pools.capacity();
+        assert!(capacity >= 40);
+
+        pools.pools.retain(|id, _| *id == TypeId::of::<[u8; 0]>());
+        pools.maybe_shrink();
+        assert!(pools.pools.capacity() < capacity);
+        assert_eq!(pools.len::<[u8; 0]>(), Some(1));
+    }
+
+    #[test]
+    fn test_collect() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3, 4]);
+
+        assert_eq!(pools.collect::<u32>(), vec![1, 2, 3, 4]);
+        assert_eq!(pools.collect_filtered::<u32, _>(|v| v % 2 == 0), vec![2, 4]);
+        assert_eq!(pools.collect::<u64>(), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_drain_where_into() {
+        let mut src = TypePools::from_pool(vec![1u32, 2, 3, 4, 5]);
+        let mut dst = TypePools::new();
+
+        assert_eq!(src.drain_where_into::<u32, _>(|v| v % 2 == 1, &mut dst), 3);
+        assert_eq!(src.type_pool::<u32>().unwrap().values, vec![2, 4]);
+        assert_eq!(dst.type_pool::<u32>().unwrap().values, vec![1, 3, 5]);
+        assert_eq!(src.drain_where_into::<u64, _>(|_| true, &mut dst), 0);
+    }
+
+    #[test]
+    fn test_drain_where_into_bookkeeping() {
+        let emptied = Rc::new(Cell::new(0));
+        let counter = emptied.clone();
+        let mut src = TypePools::from_pool(vec![1u32, 2, 3]);
+        src.on_empty::<u32, _>(move || counter.set(counter.get() + 1));
+        src.enable_recording();
+        let mut dst = TypePools::new();
+
+        assert_eq!(src.drain_where_into::<u32, _>(|v| v % 2 == 1, &mut dst), 2);
+        let indices: Vec<usize> = src.take_events().iter()
+            .map(|event| event.index)
+            .collect();
+        assert_eq!(indices, vec![2, 0]);
+        assert_eq!(emptied.get(), 0);
+        assert_eq!(src.drain_where_into::<u32, _>(|_| true, &mut dst), 1);
+        assert_eq!(emptied.get(), 1);
+        assert_eq!(dst.type_pool::<u32>().unwrap().values, vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn test_pool_metrics() {
+        let pools = TypePools::from_pool(vec![1u32, 2])
+            .with_capacity_for::<u32>(10)
+            .with_pool(vec!["a"]);
+        let metrics: &dyn PoolMetrics = &pools;
+
+        assert_eq!(metrics.type_count(), 2);
+        assert_eq!(metrics.total_values(), 3);
+        let mut entries: Vec<_> = metrics.pool_metrics().collect();
+        entries.sort();
+        assert_eq!(entries[0], ("&str", 1, pools.capacity_or_zero::<&str>()));
+        assert_eq!(entries[1].0, "u32");
+        assert_eq!(entries[1].1, 2);
+        assert!(entries[1].2 >= 10);
+    }
+
+    #[test]
+    fn test_gc() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3, 4]).with_pool(vec!["a"]);
+
+        pools.gc(|id, pool| {
+            if let Some(pool) = pool.as_any_mut().downcast_mut::<TypePool<u32>>() {
+                pool.values.retain(|v| v % 2 == 0);
+            }
+            id != TypeId::of::<&str>()
+        });
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![2, 4]);
+        assert!(!pools.contains_type::<&str>());
+    }
+
+    #[test]
+    fn test_pool_methods() {
+        let mut pools = TypePools::from_pool(vec![3i32, -1, 1, 3, 2, 1]);
+        let pool = pools.type_pool_mut::<i32>().unwrap();
+
+        pool.retain(|&x| x > 0);
+        assert_eq!(pool.values, vec![3, 1, 3, 2, 1]);
+        pool.sort();
+        pool.dedup();
+        assert_eq!(pool.values, vec![1, 2, 3]);
+        pool.truncate(2);
+        assert_eq!(pool.values, vec![1, 2]);
+        pool.clear();
+        assert_eq!(pools.len::<i32>(), Some(0));
+    }
+
+    #[test]
+    fn test_from_iter_single() {
+        let pools = TypePools::from_iter_single(0..5u32);
+
+        assert_eq!(pools.len::<u32>(), Some(5));
+        assert_eq!(pools.types_count(), 1);
+    }
+
+    #[test]
+    fn test_overwrite_at() {
+        let mut pools = TypePools::new();
+
+        pools.overwrite_at::<u32>(2, 7);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![0, 0, 7]);
+        pools.overwrite_at::<u32>(0, 1);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 0, 7]);
+    }
+
+    #[test]
+    fn test_pop_n() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3]);
+
+        assert_eq!(pools.pop_n::<u32>(2), vec![3, 2]);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1]);
+        assert_eq!(pools.pop_n::<u32>(5), vec![1]);
+        assert_eq!(pools.pop_n::<u64>(5), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_recording() {
+        let mut pools = TypePools::new();
+        pools.push(1u32);
+        pools.enable_recording();
+        pools.push(2u32);
+        pools.extend([3u32, 4]);
+        pools.push("Hello");
+        pools.remove::<u32>(1);
+        pools.pop::<&str>();
+
+        let event = |type_id, kind, index| PoolEvent { type_id, kind, index };
+        let (int, str) = (TypeId::of::<u32>(), TypeId::of::<&str>());
+        assert_eq!(pools.take_events(), vec![
+            event(int, PoolEventKind::Push, 1),
+            event(int, PoolEventKind::Push, 2),
+            event(int, PoolEventKind::Push, 3),
+            event(str, PoolEventKind::Push, 0),
+            event(int, PoolEventKind::Remove, 1),
+            event(str, PoolEventKind::Remove, 0),
+        ]);
+        assert_eq!(pools.take_events(), vec![]);
+        assert_eq!(TypePools::new().take_events(), vec![]);
+    }
+
+    #[test]
+    fn test_recording_bulk_mutations() {
+        use PoolEventKind::{Clear, Push, Remove, Set};
+
+        let mut pools = TypePools::new();
+        pools.enable_recording();
+        let kinds = |pools: &mut TypePools| -> Vec<(PoolEventKind, usize)> {
+            pools.take_events().iter()
+                .map(|event| (event.kind, event.index))
+                .collect()
+        };
+
+        pools.push_front(1u32);
+        pools.batch::<u32>(1).push(2);
+        pools.overwrite_at(0, 3u32);
+        pools.put(3, 4u32);
+        assert_eq!(kinds(&mut pools), vec![(Push, 0), (Push, 1), (Set, 0), (Push, 2), (Push, 3)]);
+
+        pools.set_values(vec![1u32, 2, 3, 4, 5]);
+        assert_eq!(kinds(&mut pools), vec![(Clear, 0), (Push, 0), (Push, 1), (Push, 2), (Push, 3), (Push, 4)]);
+
+        pools.retain_with_index::<u32, _>(|idx, _| idx % 2 == 0);
+        pools.trim_front::<u32>(1);
+        pools.truncate_all(1);
+        assert_eq!(kinds(&mut pools), vec![(Remove, 3), (Remove, 1), (Remove, 0), (Remove, 1)]);
+
+        pools.init_defaults::<u32>(2);
+        pools.drain_rev::<u32, _>(1..);
+        pools.empty_type::<u32>();
+        pools.empty_type::<u32>();
+        assert_eq!(kinds(&mut pools), vec![(Clear, 0), (Push, 0), (Push, 1), (Remove, 1), (Clear, 0)]);
+    }
+
+    #[test]
+    fn test_capacity_report() {
+        let mut pools = TypePools::new();
+        pools.reserve_exact_to::<u32>(10);
+        pools.extend([1u32, 2]);
+        pools.reserve_exact_to::<u64>(4);
+
+        let mut report = pools.capacity_report();
+        report.sort_by_key(|(_, _, capacity)| *capacity);
+        assert_eq!(report, vec![(TypeId::of::<u64>(), 0, 4), (TypeId::of::<u32>(), 2, 10)]);
+    }
+
+    #[test]
+    fn test_swap_values() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3]);
+        let mut other = vec![9u32];
+
+        pools.swap_values(&mut other);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![9]);
+        assert_eq!(other, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_get_mut_or_insert() {
+        let mut pools = TypePools::new();
+
+        *pools.get_mut_or_insert::<u32>(0, 1).unwrap() += 1;
+        *pools.get_mut_or_insert::<u32>(1, 5).unwrap() += 1;
+        *pools.get_mut_or_insert::<u32>(0, 9).unwrap() += 1;
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![3, 6]);
+        assert_eq!(pools.get_mut_or_insert::<u32>(3, 0), None);
+    }
+
+    #[test]
+    fn test_type_counts() {
+        let mut pools = TypePools::from_pool(vec![1u32]).with_pool(vec![1u64]);
+        pools.pop::<u64>();
+
+        assert_eq!(pools.nonempty_type_count(), 1);
+        assert_eq!(pools.empty_type_count(), 1);
+    }
+
+    #[test]
+    fn test_try_type_pool() {
+        let pools = TypePools::from_pool(vec![1u32]);
+
+        assert_eq!(pools.try_type_pool::<u32>().unwrap().map(|p| p.values.len()), Some(1));
+        assert!(pools.try_type_pool::<u64>().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_for_each_pool_mut() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]).with_pool(vec!["a"]);
+        let mut ids = Vec::new();
+
+        pools.for_each_pool_mut(|id, pool| {
+            ids.push(id);
+            pool.clear();
+        });
+        assert_eq!(ids.len(), 2);
+        assert_eq!(pools.total_len(), 0);
+        assert_eq!(pools.types_count(), 2);
+    }
+
+    #[test]
+    fn test_growth_policy() {
+        let mut pools = TypePools::new();
+        pools.set_growth_policy(GrowthPolicy::Exact);
+        for i in 0..10u32 {
+            pools.push(i);
+        }
+        assert_eq!(pools.capacity_or_zero::<u32>(), 10);
+
+        pools.extend(10..15u32);
+        assert_eq!(pools.capacity_or_zero::<u32>(), 15);
+
+        let mut pools = TypePools::new();
+        pools.set_growth_policy(GrowthPolicy::Amortized);
+        for i in 0..10u32 {
+            pools.push(i);
+        }
+        assert!(pools.capacity_or_zero::<u32>() >= 10);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 2, 3, 3, 3]);
+
+        let counts = pools.histogram::<u32, _, _>(|value| *value);
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&1], 1);
+        assert_eq!(counts[&2], 2);
+        assert_eq!(counts[&3], 3);
+        assert!(pools.histogram::<u8, _, _>(|value| *value).is_empty());
+    }
+
+    #[test]
+    fn test_assert_invariants() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]).with_pool(vec!["a"]);
+        pools.set_auto_prune(2);
+        pools.set_max_types(3);
+        pools.enable_recording();
+        pools.push(3u8);
+        pools.pop::<&str>();
+        pools.assert_invariants();
+
+        pools.pop::<u8>();
+        assert_eq!(pools.types_count(), 1);
+        pools.assert_invariants();
+
+        pools.make_deque::<u32>();
+        pools.push(4u32);
+        pools.assert_invariants();
+
+        pools.get_or_create_pool::<u8>().values.extend([1, 2]);
+        pools.assert_invariants();
+        pools.pop::<u8>();
+        pools.assert_invariants();
+
+        let mut other = TypePools::new();
+        other.replace_all(pools.take_all());
+        pools.assert_invariants();
+        other.assert_invariants();
+        let mut pools = TypePools::from_inner(other.into_inner());
+        pools.push(1u16);
+        pools.set_max_types(1);
+        assert_eq!(pools.types_count(), 1);
+        pools.assert_invariants();
+    }
+
+    #[test]
+    fn test_set_max_types_evicts() {
+        let mut pools = TypePools::from_pool(vec![1u32]).with_pool(vec![1u8]);
+        pools.register_empty::<u16>();
+        pools.push(2u8);
+        pools.set_max_types(2);
+        assert!(!pools.contains_type::<u16>());
+        pools.set_max_types(1);
+        assert!(pools.contains_type::<u8>());
+
+        let mut pools = TypePools::new();
+        pools.set_max_types(1);
+        pools.replace_all(TypePools::from_pool(vec![1u32]).with_pool(vec![1u8]));
+        assert_eq!(pools.types_count(), 1);
+    }
+
+    #[test]
+    fn test_into_values() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3]).with_pool(vec![String::from("a")]);
+        assert_eq!(pools.into_values::<u32>(), vec![1, 2, 3]);
+
+        let pools = TypePools::from_pool(vec![String::from("a")]);
+        assert!(pools.into_values::<u32>().is_empty());
+    }
+
+    #[test]
+    fn test_chunks_exact_rchunks() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3, 4, 5, 6, 7]);
+
+        let chunks = pools.chunks_exact::<u32>(2).unwrap();
+        assert_eq!(chunks.remainder(), &[7]);
+        assert_eq!(chunks.collect::<Vec<_>>(), vec![&[1, 2][..], &[3, 4], &[5, 6]]);
+
+        let chunks: Vec<_> = pools.rchunks::<u32>(3).unwrap().collect();
+        assert_eq!(chunks, vec![&[5, 6, 7][..], &[2, 3, 4], &[1]]);
+        assert!(pools.chunks_exact::<u8>(2).is_none());
+        assert!(pools.rchunks::<u8>(2).is_none());
+    }
+
+    #[test]
+    fn test_first_n_last_n() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3, 4]);
+
+        assert_eq!(pools.first_n::<u32>(2), Some(&[1, 2][..]));
+        assert_eq!(pools.last_n::<u32>(2), Some(&[3, 4][..]));
+        assert_eq!(pools.first_n::<u32>(10), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(pools.last_n::<u32>(10), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(pools.first_n::<u8>(2), None);
+    }
+
+    #[test]
+    fn test_swap_remove_tracked() {
+        let mut pools = TypePools::from_pool(vec!["a", "b", "c"]);
+
+        assert_eq!(pools.swap_remove_tracked::<&str>(0), Some(("a", Some(2))));
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["c", "b"]);
+        assert_eq!(pools.swap_remove_tracked::<&str>(1), Some(("b", None)));
+        assert_eq!(pools.swap_remove_tracked::<&str>(1), None);
+        assert_eq!(pools.swap_remove_tracked::<u8>(0), None);
+    }
+
+    #[test]
+    fn test_pools_over_capacity() {
+        let mut pools = TypePools::new();
+        pools.reserve_exact_to::<u32>(100);
+        pools.reserve_exact_to::<u8>(4);
+
+        assert_eq!(pools.pools_over_capacity(50), vec![TypeId::of::<u32>()]);
+        assert!(pools.pools_over_capacity(100).is_empty());
+    }
+
+    #[test]
+    fn test_empty_type_remove_type() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]).with_pool(vec!["a"]);
+
+        pools.empty_type::<u32>();
+        assert!(pools.contains_type::<u32>());
+        assert_eq!(pools.len::<u32>(), Some(0));
+
+        pools.remove_type::<&str>();
+        assert!(!pools.contains_type::<&str>());
+        assert!(pools.type_pool::<&str>().is_none());
+    }
+
+    #[test]
+    fn test_reserve_to_peak() {
+        let mut pools = TypePools::new();
+        pools.extend(0..100u32);
+        pools.empty_type::<u32>();
+        pools.shrink_all();
+        assert_eq!(pools.capacity_or_zero::<u32>(), 0);
+
+        pools.reserve_to_peak::<u32>();
+        assert!(pools.capacity_or_zero::<u32>() >= 100);
+    }
+
+    #[test]
+    fn test_get_cloned() {
+        let mut pools = TypePools::from_pool(vec![String::from("a")]).with_pool(vec![1u32]);
+
+        let value = pools.get_cloned::<String>(0);
+        *pools.get_mut::<u32>(0).unwrap() += 1;
+        assert_eq!(value.as_deref(), Some("a"));
+        assert_eq!(pools.get::<u32>(0), Some(&2));
+        assert_eq!(pools.get_cloned::<String>(1), None);
+    }
+
+    #[test]
+    fn test_push_mut() {
+        #[derive(Default)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let mut pools = TypePools::new();
+        pools.push_mut(Point::default()).x = 3;
+        pools.push_mut(Point::default()).y = 4;
+        assert_eq!(pools.get::<Point>(0).map(|p| (p.x, p.y)), Some((3, 0)));
+        assert_eq!(pools.get::<Point>(1).map(|p| (p.x, p.y)), Some((0, 4)));
+
+        pools.make_deque::<u32>();
+        *pools.push_mut(1u32) += 1;
+        assert_eq!(pools.type_deque::<u32>().unwrap().back(), Some(&2));
+    }
+
+    #[test]
+    fn test_any_nonempty() {
+        let mut pools = TypePools::new();
+        assert!(!pools.any_nonempty());
+
+        pools.reserve_exact_to::<u32>(4);
+        assert!(!pools.any_nonempty());
+
+        pools.push("a");
+        assert!(pools.any_nonempty());
+    }
+
+    #[test]
+    fn test_sweep_registered() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3, 4]).with_pool(vec!["a", "bb", "c"]).with_pool(vec![1u8]);
+        let mut registry = SweepRegistry::new();
+        registry.register::<u32, _>(|v| v % 2 == 0);
+        registry.register::<&str, _>(|v| v.len() > 1);
+        registry.register::<i64, _>(|_| true);
+
+        let mut swept = pools.sweep_registered(&mut registry);
+        assert_eq!(swept.take::<u32>(), vec![2, 4]);
+        assert_eq!(swept.take::<&str>(), vec!["bb"]);
+        assert!(swept.take::<u8>().is_empty());
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 3]);
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["a", "c"]);
+        assert_eq!(pools.type_pool::<u8>().unwrap().values, vec![1]);
+    }
+
+    #[test]
+    fn test_min_max() {
+        let pools = TypePools::from_pool(vec![3i32, 1, 5, -2]);
+
+        assert_eq!(pools.max::<i32>(), Some(&5));
+        assert_eq!(pools.min::<i32>(), Some(&-2));
+        assert_eq!(pools.min_by_key::<i32, _, _>(|v| v.abs()), Some(&1));
+        assert_eq!(pools.max_by_key::<i32, _, _>(|v| -v), Some(&-2));
+        assert_eq!(pools.max::<u8>(), None);
+    }
+
+    #[test]
+    fn test_hint_capacity() {
+        let mut pools = TypePools::new();
+        pools.hint_capacity::<u32>(64);
+        pools.push(1u32);
+        assert!(pools.capacity_or_zero::<u32>() >= 64);
+
+        pools.hint_capacity::<u32>(1000);
+        pools.hint_capacity::<u8>(32);
+        pools.extend([1u8, 2]);
+        assert!(pools.capacity_or_zero::<u32>() < 1000);
+        assert!(pools.capacity_or_zero::<u8>() >= 32);
+    }
+
+    #[test]
+    fn test_eq_unordered() {
+        let a = TypePools::from_pool(vec![1u32, 2, 3, 2]);
+        let b = TypePools::from_pool(vec![2u32, 3, 2, 1]);
+        let c = TypePools::from_pool(vec![1u32, 2, 3]);
+
+        assert!(a.eq_unordered::<u32>(&b));
+        assert!(!a.eq_unordered::<u32>(&c));
+        assert!(a.eq_unordered::<u8>(&b));
+    }
+
+    #[test]
+    fn test_compact_type() {
+        let mut pools = TypePools::from_pool(vec![1u32]).with_pool(vec![1u8, 2, 3]);
+        pools.pop::<u32>();
+        pools.reserve_all(100);
+
+        assert!(pools.compact_type::<u32>());
+        assert!(!pools.contains_type::<u32>());
+        assert!(!pools.compact_type::<u8>());
+        assert_eq!(pools.capacity_or_zero::<u8>(), 3);
+        assert!(!pools.compact_type::<u16>());
+    }
+
+    #[test]
+    fn test_retain_update() {
+        struct Particle {
+            id: u32,
+            ttl: u32,
+        }
+
+        let mut pools = TypePools::from_pool(vec![Particle { id: 0, ttl: 1 }, Particle { id: 1, ttl: 3 }, Particle { id: 2, ttl: 1 }]);
+        pools.retain_update::<Particle, _>(|p| {
+            p.ttl -= 1;
+            if p.ttl == 0 { Keep::No } else { Keep::Yes }
+        });
+
+        let pool = pools.type_pool::<Particle>().unwrap();
+        assert_eq!(pool.values.len(), 1);
+        assert_eq!((pool.values[0].id, pool.values[0].ttl), (1, 2));
+    }
+
+    #[test]
+    fn test_push_slice() {
+        let bytes: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        let mut pools = TypePools::from_pool(vec![7u8]);
+        pools.push_slice(&bytes);
+
+        let pool = pools.type_pool::<u8>().unwrap();
+        assert_eq!(pool.values.len(), 10_001);
+        assert_eq!(&pool.values[1..], &bytes[..]);
+        assert!(pool.values.capacity() >= 10_001);
+    }
+
+    #[test]
+    fn test_push_slice_touches_pool() {
+        let mut pools = TypePools::new();
+        pools.set_max_types(2);
+        pools.push(1u32);
+        pools.push(1u64);
+        pools.push_slice(&[2u32]);
+        pools.push(1u8);
+
+        assert!(pools.contains_type::<u32>());
+        assert!(!pools.contains_type::<u64>());
+    }
+
+    #[test]
+    fn test_with_pool_mut() {
+        let mut pools = TypePools::new();
+        let sum = pools.with_pool_mut::<u32, _, _>(|pool| {
+            pool.values.extend([1, 2, 3]);
+            pool.values.iter().sum::<u32>()
+        });
+        assert_eq!(sum, 6);
+        assert_eq!(pools.len::<u32>(), Some(3));
+    }
+
+    #[test]
+    fn test_pairs() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3]);
+
+        let pairs: Vec<_> = pools.pairs::<u32>().unwrap().collect();
+        assert_eq!(pairs, vec![(&1, &2), (&1, &3), (&2, &3)]);
+        assert!(pools.pairs::<u8>().is_none());
+    }
+
+    #[test]
+    fn test_migrate() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3]);
+        assert_eq!(pools.migrate::<u32, String, _, ()>(|v| Ok(v.to_string())), Ok(()));
+        assert!(!pools.contains_type::<u32>());
+        assert_eq!(pools.type_pool::<String>().unwrap().values, vec!["1", "2", "3"]);
+
+        let mut pools = TypePools::from_pool(vec![1i32, -2, 3]);
+        let result = pools.migrate::<i32, u32, _, _>(|v| u32::try_from(v).map_err(|_| v));
+        assert_eq!(result, Err(-2));
+        assert_eq!(pools.type_pool::<i32>().unwrap().values, vec![3]);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1]);
+    }
+
+    #[test]
+    fn test_all_lengths_equal() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]).with_pool(vec!["a", "b"]);
+        pools.ensure_pool(TypeId::of::<u8>(), &{
+            let mut registry = ConstructorRegistry::new();
+            registry.register::<u8>();
+            registry
+        });
+        assert!(pools.all_lengths_equal());
+        assert_eq!(pools.max_len(), 2);
+
+        pools.push(3u32);
+        assert!(!pools.all_lengths_equal());
+        assert_eq!(pools.max_len(), 3);
+        assert!(TypePools::new().all_lengths_equal());
+    }
+
+    #[test]
+    fn test_push_default() {
+        let mut pools = TypePools::from_pool(vec![5u32]);
+
+        let idx = pools.push_default::<u32>();
+        assert_eq!(idx, 1);
+        assert_eq!(pools.get::<u32>(idx), Some(&0));
+        assert_eq!(pools.push_default::<String>(), 0);
+    }
+
+    #[test]
+    fn test_reinterpret() {
+        #[derive(Debug, PartialEq)]
+        struct Wrapper(u32);
+
+        impl From<u32> for Wrapper {
+            fn from(value: u32) -> Self {
+                Wrapper(value)
+            }
+        }
+
+        let mut pools = TypePools::from_pool(vec![1u32, 2]);
+        assert_eq!(pools.reinterpret::<u32, Wrapper>(), Some(()));
+        assert!(!pools.contains_type::<u32>());
+        assert_eq!(pools.type_pool::<Wrapper>().unwrap().values, vec![Wrapper(1), Wrapper(2)]);
+        assert_eq!(pools.reinterpret::<u32, Wrapper>(), None);
+    }
+
+    #[test]
+    fn test_truncate_to_lengths() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]).with_pool(vec!["a"]);
+        let snapshot = pools.snapshot_lengths();
+
+        pools.extend([3u32, 4]);
+        pools.push("b");
+        pools.push(1u8);
+        pools.truncate_to_lengths(&snapshot);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 2]);
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["a"]);
+        assert!(!pools.contains_type::<u8>());
+    }
+
+    #[test]
+    fn test_with_element_mut() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]);
+
+        let value = pools.with_element_mut::<u32, _, _>(1, |v| {
+            *v += 1;
+            *v
+        });
+        assert_eq!(value, Some(3));
+        assert_eq!(pools.get::<u32>(1), Some(&3));
+        assert_eq!(pools.with_element_mut::<u32, _, _>(2, |v| *v), None);
+    }
+
+    #[test]
+    fn test_get_or_create_pool() {
+        let mut pools = TypePools::new();
+        pools.get_or_create_pool::<u32>().values.push(1);
+        pools.get_or_create_pool::<u32>().values.push(2);
+        assert_eq!(pools.types_count(), 1);
+
+        pools.push(3u32);
+        assert_eq!(pools.get_or_create_pool::<u32>().values, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_truncate_all() {
+        let mut pools = TypePools::new();
+        pools.extend(0..5u32);
+        pools.extend(0..10u8);
+        pools.extend(0..2u16);
+
+        pools.truncate_all(3);
+        assert_eq!(pools.len::<u32>(), Some(3));
+        assert_eq!(pools.len::<u8>(), Some(3));
+        assert_eq!(pools.len::<u16>(), Some(2));
+    }
+
+    #[test]
+    fn test_contains_index() {
+        let pools = TypePools::from_pool(vec![1u32, 2]);
+
+        assert!(pools.contains_index::<u32>(1));
+        assert!(!pools.contains_index::<u32>(2));
+        assert!(!pools.contains_index::<u8>(0));
+    }
+
+    #[test]
+    fn test_take_all() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]).with_pool(vec!["a"]);
+        pools.make_deque::<u8>();
+        pools.push_back(1u8);
+
+        let taken = pools.take_all();
+        assert_eq!(pools.types_count(), 0);
+        assert!(pools.type_deque::<u8>().is_none());
+        assert_eq!(taken.type_pool::<u32>().unwrap().values, vec![1, 2]);
+        assert_eq!(taken.type_pool::<&str>().unwrap().values, vec!["a"]);
+        assert_eq!(taken.type_deque::<u8>().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_merge_preferring_longer() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]).with_pool(vec!["a", "b"]);
+        let other = TypePools::from_pool(vec![3u32, 4, 5]).with_pool(vec!["c"]).with_pool(vec![1u8]);
+
+        pools.merge_preferring_longer(other);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![3, 4, 5]);
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["a", "b"]);
+        assert_eq!(pools.type_pool::<u8>().unwrap().values, vec![1]);
+    }
+
+    #[test]
+    fn test_sorted_type_ids() {
+        let a = TypePools::from_pool(vec![1u32]).with_pool(vec!["a"]).with_pool(vec![1u8]);
+        let b = TypePools::from_pool(vec![1u8]).with_pool(vec![1u32]).with_pool(vec!["a"]);
+
+        let ids = a.sorted_type_ids();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids, b.sorted_type_ids());
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_on_empty() {
+        let fired = Rc::new(Cell::new(0));
+        let mut pools = TypePools::from_pool(vec![1u32, 2]).with_pool(vec!["a", "b"]);
+        let counter = fired.clone();
+        pools.on_empty::<u32, _>(move || counter.set(counter.get() + 1));
+
+        pools.remove::<u32>(0);
+        assert_eq!(fired.get(), 0);
+        pools.pop::<u32>();
+        assert_eq!(fired.get(), 1);
+        assert_eq!(pools.pop::<u32>(), None);
+        assert_eq!(fired.get(), 1);
+
+        pools.push(3u32);
+        pools.retain_count::<u32, _>(|_| false);
+        assert_eq!(fired.get(), 2);
+        pools.pop::<&str>();
+        pools.pop::<&str>();
+        assert_eq!(fired.get(), 2);
+    }
+
+    #[test]
+    fn test_reserve_like() {
+        let mut pools = TypePools::from_pool(vec![1u32]).with_pool(vec![1u8]);
+        let mut other = TypePools::new();
+        other.extend(0..100u32);
+        other.extend(0..10u16);
+
+        pools.reserve_like(&other);
+        assert!(pools.capacity_or_zero::<u32>() >= 101);
+        assert!(!pools.contains_type::<u16>());
+    }
+
+    #[test]
+    fn test_drain_rev() {
+        let mut pools = TypePools::from_pool(vec!["a", "b", "c", "d", "e"]);
+
+        assert_eq!(pools.drain_rev::<&str, _>(1..4), Some(vec!["d", "c", "b"]));
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["a", "e"]);
+        assert_eq!(pools.drain_rev::<u8, _>(..), None);
+    }
+
+    #[test]
+    fn test_count_types() {
+        let pools = TypePools::from_pool(vec![1u32]).with_pool(vec!["a"]).with_pool(vec![1u8]);
+        let allowed = [TypeId::of::<u32>(), TypeId::of::<u8>(), TypeId::of::<u16>()];
+
+        assert_eq!(pools.count_types(|id| allowed.contains(&id)), 2);
+        assert_eq!(pools.count_types(|_| false), 0);
+    }
+
+    #[test]
+    fn test_extend_indexed() {
+        let mut pools = TypePools::from_pool(vec![10u32, 11]);
+
+        assert_eq!(pools.extend_indexed(0..5u32), 2..7);
+        assert_eq!(pools.get::<u32>(2), Some(&0));
+        assert_eq!(pools.extend_indexed(Vec::<u8>::new()), 0..0);
+    }
+
+    #[test]
+    fn test_element_size() {
+        let pools = TypePools::from_pool(vec![1u64]).with_pool(vec![[0u8; 3]]);
+
+        assert_eq!(pools.element_size(TypeId::of::<u64>()), Some(8));
+        assert_eq!(pools.element_size(TypeId::of::<[u8; 3]>()), Some(3));
+        assert_eq!(pools.element_size(TypeId::of::<u32>()), None);
+    }
+
+    #[test]
+    fn test_try_for_each_mut() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 10, 3]);
+
+        let result = pools.try_for_each_mut::<u32, _, _>(|v| {
+            if *v > 5 {
+                return Err(*v);
+            }
+            *v *= 2;
+            Ok(())
+        });
+        assert_eq!(result, Err(10));
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![2, 4, 10, 3]);
+        assert_eq!(pools.try_for_each_mut::<u8, (), _>(|_| Ok(())), Ok(None));
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3]);
+        let other = TypePools::from_pool(vec![1u32, 2, 3]);
+
+        let checksum = pools.checksum::<u32>();
+        assert!(checksum.is_some());
+        assert_eq!(checksum, other.checksum::<u32>());
+        *pools.get_mut::<u32>(0).unwrap() = 4;
+        assert_ne!(checksum, pools.checksum::<u32>());
+        assert_eq!(pools.checksum::<u8>(), None);
+    }
+
+    #[test]
+    fn test_dedup_unordered() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 1, 3, 2]);
+
+        pools.dedup_unordered::<u32>();
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_replace_all() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]);
+
+        let old = pools.replace_all(TypePools::from_pool(vec!["a"]));
+        assert_eq!(old.type_pool::<u32>().unwrap().values, vec![1, 2]);
+        assert!(!old.contains_type::<&str>());
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["a"]);
+        assert!(!pools.contains_type::<u32>());
+    }
+
+    #[test]
+    fn test_all_present_nonempty() {
+        let mut pools = TypePools::from_pool(vec![1u32]);
+        pools.reserve_exact_to::<u8>(1);
+        let required = [TypeId::of::<u32>(), TypeId::of::<u8>()];
+
+        assert!(!pools.all_present_nonempty(&required));
+        pools.push(1u8);
+        assert!(pools.all_present_nonempty(&required));
+        assert!(!pools.all_present_nonempty(&[TypeId::of::<u16>()]));
+    }
+
+    #[test]
+    fn test_retain_collecting() {
+        let mut pools = TypePools::from_pool(vec![1i32, -2, 3, -4]);
+
+        assert_eq!(pools.retain_collecting::<i32, _>(|v| *v > 0), vec![-2, -4]);
+        assert_eq!(pools.type_pool::<i32>().unwrap().values, vec![1, 3]);
+        assert!(pools.retain_collecting::<u8, _>(|_| true).is_empty());
+    }
+
+    #[test]
+    fn test_get_many() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3]);
+
+        assert_eq!(pools.get_many::<u32>(&[0, 5, 2, 0]), vec![Some(&1), None, Some(&3), Some(&1)]);
+        assert_eq!(pools.get_many::<u8>(&[0]), vec![None]);
+    }
+
+    #[test]
+    fn test_register_empty() {
+        let mut pools = TypePools::from_pool(vec![1u8]);
+        assert!(!pools.is_registered::<u32>());
+
+        pools.register_empty::<u32>();
+        assert!(pools.is_registered::<u32>());
+        assert_eq!(pools.type_pool::<u32>().map(|p| p.values.len()), Some(0));
+
+        pools.register_empty::<u8>();
+        assert_eq!(pools.len::<u8>(), Some(1));
+    }
+
+    #[test]
+    fn test_move_pool() {
+        let mut src = TypePools::from_pool(vec![String::from("a"), String::from("b")]).with_pool(vec![1u8]);
+        let mut dst = TypePools::from_pool(vec![String::from("c")]);
+
+        assert!(src.move_pool::<String>(&mut dst));
+        assert!(!src.contains_type::<String>());
+        assert_eq!(dst.type_pool::<String>().unwrap().values, vec!["a", "b"]);
+        assert!(!src.move_pool::<u32>(&mut dst));
+    }
+
+    #[test]
+    fn test_chain_iter() {
+        let a = TypePools::from_pool(vec![1u32, 2]);
+        let b = TypePools::from_pool(vec!["a"]);
+        let c = TypePools::from_pool(vec![3u32]);
+
+        let chunks = [&a, &b, &c];
+        let values: Vec<_> = TypePools::chain_iter::<u32>(&chunks).collect();
+        assert_eq!(values, vec![&1, &2, &3]);
+    }
+
+    #[test]
+    fn test_truncate_returning() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3, 4, 5]);
+
+        assert_eq!(pools.truncate_returning::<u32>(2), vec![3, 4, 5]);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 2]);
+        assert!(pools.truncate_returning::<u32>(5).is_empty());
+        assert!(pools.truncate_returning::<u8>(0).is_empty());
+    }
+
+    #[test]
+    fn test_memory_breakdown() {
+        let mut pools = TypePools::new();
+        pools.reserve_exact_to::<u32>(32);
+        pools.extend(0..10u32);
+
+        let breakdown = pools.memory_breakdown();
+        assert_eq!(breakdown.used, 40);
+        assert_eq!(breakdown.reserved, 88);
+        assert!(breakdown.map_overhead > 0);
+        assert_eq!(breakdown.used + breakdown.reserved, pools.memory_usage());
+    }
+
+    #[test]
+    fn test_push_many_default() {
+        let mut pools = TypePools::from_pool(vec![7u32, 8]);
+
+        assert_eq!(pools.push_many_default::<u32>(3), 2..5);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![7, 8, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_for_each_mut_indexed() {
+        let mut pools = TypePools::from_pool(vec![9u32; 4]);
+
+        assert_eq!(pools.for_each_mut_indexed::<u32, _>(|idx, v| *v = idx as u32), Some(()));
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![0, 1, 2, 3]);
+        assert_eq!(pools.for_each_mut_indexed::<u8, _>(|_, _| {}), None);
+    }
+
+    #[test]
+    fn test_find_with_index() {
+        let pools = TypePools::from_pool(vec![1u32, 2, 3, 4]);
+
+        assert_eq!(pools.find_with_index::<u32, _>(|v| *v > 2), Some((2, &3)));
+        assert_eq!(pools.find_with_index::<u32, _>(|v| *v > 4), None);
+        assert_eq!(pools.find_with_index::<u8, _>(|_| true), None);
+    }
+
+    #[test]
+    fn test_swap_remove_where() {
+        let mut pools = TypePools::from_pool(vec![1u32, 3, 4, 5]);
+
+        assert_eq!(pools.swap_remove_where::<u32, _>(|v| v % 2 == 0), Some(4));
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![1, 3, 5]);
+        assert_eq!(pools.swap_remove_where::<u32, _>(|v| v % 2 == 0), None);
+        assert_eq!(pools.swap_remove_where::<u8, _>(|_| true), None);
+    }
+
+    #[test]
+    fn test_clone_shape() {
+        let mut pools = TypePools::from_pool(vec!["a"]);
+        pools.reserve_exact_to::<u32>(100);
+        pools.extend(0..10u32);
+
+        let shape = pools.clone_shape();
+        assert_eq!(shape.types_count(), 2);
+        assert_eq!(shape.len::<u32>(), Some(0));
+        assert!(shape.capacity_or_zero::<u32>() >= 100);
+        assert_eq!(shape.len::<&str>(), Some(0));
+    }
+
+    #[test]
+    fn test_put() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]);
+
+        assert_eq!(pools.put(0, 5u32), Some(1));
+        assert_eq!(pools.put(2, 9u32), None);
+        assert_eq!(pools.put(5, 7u32), None);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![5, 2, 9, 0, 0, 7]);
+        assert_eq!(pools.put(1, 3u8), None);
+        assert_eq!(pools.type_pool::<u8>().unwrap().values, vec![0, 3]);
+    }
+
+    #[test]
+    fn test_visit_all_mut() {
+        let mut pools = TypePools::from_pool(vec![1u32, 2]).with_pool(vec![3i8, -4]).with_pool(vec!["a"]);
+        let mut registry = VisitorRegistry::new();
+        registry.add_visitor::<u32, _>(|values| values.iter_mut().for_each(|v| *v *= 2));
+        registry.add_visitor::<i8, _>(|values| values.iter_mut().for_each(|v| *v = -*v));
+        registry.add_visitor::<u64, _>(|_| panic!("no type pool for u64"));
+
+        pools.visit_all_mut(&mut registry);
+        assert_eq!(pools.type_pool::<u32>().unwrap().values, vec![2, 4]);
+        assert_eq!(pools.type_pool::<i8>().unwrap().values, vec![-3, 4]);
+        assert_eq!(pools.type_pool::<&str>().unwrap().values, vec!["a"]);
+    }
+
+    #[test]
+    fn test_deque_methods() {
+        let deque = || {
+            let mut pools = TypePools::new();
+            pools.make_deque::<u32>();
+            pools.extend([2u32, 3]);
+            pools.push_front(1u32);
+            pools
+        };
+
+        let mut pools = deque();
+        assert_eq!(pools.checked_get::<u32>(2), Ok(&3));
+        assert_eq!(pools.checked_get::<u32>(3), Err(PoolError::IndexOutOfBounds { idx: 3, len: 3 }));
+        assert_eq!(pools.collect::<u32>(), vec![1, 2, 3]);
+        assert_eq!(pools.rposition::<u32, _>(|v| *v < 3), Some(1));
+        assert_eq!(pools.is_sorted::<u32>(), Some(true));
+        assert_eq!(pools.pop::<u32>(), Some(3));
+        assert_eq!(pools.remove::<u32>(0), Some(1));
+        assert_eq!(pools.single::<u32>(), Some(&2));
+        assert_eq!(pools.set_values(vec![4u32, 5]), Some(vec![2]));
+        pools.batch::<u32>(1).push(6);
+        pools.overwrite_at(4, 8u32);
+        assert_eq!(pools.put(5, 9u32), None);
+        assert_eq!(pools.swap_remove_value(&4u32), Some(4));
+        assert_eq!(pools.type_deque::<u32>().unwrap(), &[9, 5, 6, 0, 8]);
+
+        let mut pools = deque();
+        pools.reserve_exact_to::<u32>(10);
+        assert!(pools.capacity_or_zero::<u32>() >= 10);
+        pools.init_defaults::<u32>(2);
+        let mut other = vec![7u32];
+        pools.swap_values(&mut other);
+        assert_eq!(other, vec![0, 0]);
+        assert_eq!(pools.type_deque::<u32>().unwrap(), &[7]);
+
+        let mut pools = deque();
+        assert_eq!(pools.migrate::<u32, u64, _, ()>(|v| Ok(v as u64)), Ok(()));
+        assert!(!pools.contains_type::<u32>());
+        assert_eq!(pools.collect::<u64>(), vec![1, 2, 3]);
+
+        let mut pools = deque().with_capacity_for::<u32>(8);
+        assert_eq!(pools.type_deque::<u32>().unwrap(), &[1, 2, 3]);
+        assert_eq!(pools.entry_or_insert_with::<u32, _>(Vec::new).values, vec![1, 2, 3]);
+        assert_eq!(pools.type_deque::<u32>(), None);
+    }
+
+    #[test]
+    fn test_recording_swap_remove() {
+        let mut pools = TypePools::from_pool(vec!['a', 'b', 'c', 'd', 'e', 'f']);
+        let mut replayed = pools.collect::<char>();
+        pools.enable_recording();
+
+        pools.swap_remove_value(&'a');
+        pools.swap_remove_where::<char, _>(|c| *c == 'c');
+        pools.swap_remove_tracked::<char>(3);
+        pools.swap_remove_many::<char>(&[0, 1]);
+        pools.remove::<char>(0);
+
+        for event in pools.take_events() {
+            match event.kind {
+                PoolEventKind::SwapRemove => { replayed.swap_remove(event.index); }
+                PoolEventKind::Remove => { replayed.remove(event.index); }
+                kind => panic!("unexpected event {:?}", kind),
+            }
+        }
+        assert_eq!(replayed, pools.collect::<char>());
+    }
+
+    #[test]
+    fn test_overwrite_at_notifies() {
+        let pushed = Rc::new(Cell::new(0));
+        let mut pools = TypePools::new();
+        let counter = pushed.clone();
+        pools.on_push::<u32, _>(move |_| counter.set(counter.get() + 1));
+        pools.enable_recording();
+
+        pools.overwrite_at(2, 7u32);
+        pools.overwrite_at(1, 5u32);
+        assert_eq!(pools.collect::<u32>(), vec![0, 5, 7]);
+        assert_eq!(pushed.get(), 3);
+        let kinds: Vec<_> = pools.take_events().iter()
+            .map(|event| (event.kind, event.index))
+            .collect();
+        assert_eq!(kinds, vec![(PoolEventKind::Push, 0), (PoolEventKind::Push, 1), (PoolEventKind::Push, 2), (PoolEventKind::Set, 1)]);
+    }
+
+    #[test]
+    fn test_put_notifies() {
+        let pushed = Rc::new(Cell::new(0));
+        let mut pools = TypePools::from_pool(vec![1u32]);
+        let counter = pushed.clone();
+        pools.on_push::<u32, _>(move |_| counter.set(counter.get() + 1));
+        pools.enable_recording();
+
+        pools.put(1, 2u32);
+        pools.put(3, 4u32);
+        pools.put(0, 5u32);
+        assert_eq!(pools.collect::<u32>(), vec![5, 2, 0, 4]);
+        assert_eq!(pushed.get(), 3);
+        let kinds: Vec<_> = pools.take_events().iter()
+            .map(|event| (event.kind, event.index))
+            .collect();
+        assert_eq!(kinds, vec![(PoolEventKind::Push, 1), (PoolEventKind::Push, 2), (PoolEventKind::Push, 3), (PoolEventKind::Set, 0)]);
+    }
+
+    #[test]
+    fn test_retain_count_notifies() {
+        let removed = Rc::new(Cell::new(0));
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3, 4]);
+        let sum = removed.clone();
+        pools.on_remove::<u32, _>(move |v| sum.set(sum.get() + v));
+
+        assert_eq!(pools.retain_count::<u32, _>(|v| v % 2 == 0), 2);
+        assert_eq!(removed.get(), 4);
+        pools.retain_update::<u32, _>(|v| if *v == 2 { Keep::Yes } else { Keep::No });
+        assert_eq!(removed.get(), 8);
+    }
+
+    #[test]
+    fn test_retain_collecting_notifies() {
+        let removed = Rc::new(Cell::new(0));
+        let emptied = Rc::new(Cell::new(0));
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3]);
+        let (sum, count) = (removed.clone(), emptied.clone());
+        pools.on_remove::<u32, _>(move |v| sum.set(sum.get() + v));
+        pools.on_empty::<u32, _>(move || count.set(count.get() + 1));
+
+        assert_eq!(pools.retain_collecting::<u32, _>(|v| *v == 2), vec![1, 3]);
+        assert_eq!((removed.get(), emptied.get()), (4, 0));
+        assert_eq!(pools.retain_collecting::<u32, _>(|_| false), vec![2]);
+        assert_eq!((removed.get(), emptied.get()), (6, 1));
+    }
+
+    #[test]
+    fn test_trim_notifies() {
+        let removed = Rc::new(Cell::new(0));
+        let emptied = Rc::new(Cell::new(0));
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3, 4]).with_pool(vec![1u8]);
+        let (sum, count) = (removed.clone(), emptied.clone());
+        pools.on_remove::<u32, _>(move |v| sum.set(sum.get() + v));
+        pools.on_empty::<u32, _>(move || count.set(count.get() + 1));
+        pools.set_auto_prune(1);
+
+        pools.trim_front::<u32>(1);
+        pools.trim_back::<u32>(1);
+        assert_eq!((removed.get(), emptied.get()), (5, 0));
+        pools.trim_back::<u32>(5);
+        assert_eq!((removed.get(), emptied.get()), (10, 1));
+        assert!(!pools.contains_type::<u32>());
+        pools.trim_front::<u8>(3);
+        assert_eq!(pools.types_count(), 0);
+    }
+
+    #[test]
+    fn test_truncate_returning_notifies() {
+        let removed = Rc::new(Cell::new(0));
+        let emptied = Rc::new(Cell::new(0));
+        let mut pools = TypePools::from_pool(vec![1u32, 2, 3]).with_pool(vec![1u8]);
+        let (sum, count) = (removed.clone(), emptied.clone());
+        pools.on_remove::<u32, _>(move |v| sum.set(sum.get() + v));
+        pools.on_empty::<u32, _>(move || count.set(count.get() + 1));
+        pools.set_auto_prune(2);
+
+        assert_eq!(pools.truncate_returning::<u32>(1), vec![2, 3]);
+        assert_eq!((removed.get(), emptied.get()), (5, 0));
+        pools.pop::<u8>();
+        assert_eq!(pools.truncate_returning::<u32>(0), vec![1]);
+        assert_eq!((removed.get(), emptied.get()), (6, 1));
+        assert_eq!(pools.types_count(), 0);
+    }
+
+    #[test]
+    fn test_drain_notifies() {
+        let removed = Rc::new(Cell::new(0));
+        let emptied = Rc::new(Cell::new(0));
+        let mut pools = TypePools::from_pool(vec![3u32, 1, 2]);
+        let (sum, count) = (removed.clone(), emptied.clone());
+        pools.on_remove::<u32, _>(move |v| sum.set(sum.get() + v));
+        pools.on_empty::<u32, _>(move || count.set(count.get() + 1));
+
+        assert_eq!(pools.drain_rev::<u32, _>(2..), Some(vec![2]));
+        assert_eq!((removed.get(), emptied.get()), (2, 0));
+        assert_eq!(pools.drain_rev::<u32, _>(..1), Some(vec![3]));
+        pools.push(5u32);
+        assert_eq!(pools.drain_sorted::<u32>(), Some(vec![1, 5]));
+        assert_eq!((removed.get(), emptied.get()), (11, 1));
+        pools.set_auto_prune(1);
+        pools.push(4u32);
+        assert_eq!(pools.drain_rev::<u32, _>(..), Some(vec![4]));
+        assert_eq!((removed.get(), emptied.get()), (15, 2));
+        assert!(!pools.contains_type::<u32>());
+    }
+
+    #[test]
+    fn test_on_empty_bulk_removals() {
+        let fired = Rc::new(Cell::new(0));
+        let mut pools = TypePools::new();
+        let counter = fired.clone();
+        pools.on_empty::<u32, _>(move || counter.set(counter.get() + 1));
+        let removals: [fn(&mut TypePools); 6] = [
+            |pools| pools.retain_with_index::<u32, _>(|idx, _| idx > 5),
+            |pools| pools.retain_update::<u32, _>(|_| Keep::No),
+            |pools| { pools.truncate_returning::<u32>(0); },
+            |pools| pools.trim_front::<u32>(3),
+            |pools| pools.trim_back::<u32>(5),
+            |pools| { pools.swap_remove_many::<u32>(&[0, 1]); },
+        ];
+
+        for (i, remove) in removals.iter().enumerate() {
+            pools.extend([1u32, 2]);
+            remove(&mut pools);
+            assert_eq!(fired.get(), i + 1);
+            remove(&mut pools);
+            assert_eq!(fired.get(), i + 1);
+        }
+    }
+
+    #[test]
+    fn test_empty_type_count_is_tracked() {
+        let mut pools = TypePools::from_pool(vec![1u32]);
+        let scanned = |pools: &TypePools| pools.types_count() - pools.nonempty_type_count();
+
+        pools.register_empty::<u8>();
+        pools.make_deque::<u16>();
+        assert_eq!(pools.empty_type_count(), 2);
+        pools.pop::<u32>();
+        pools.push_front(1u16);
+        pools.batch::<u8>(1).push(1);
+        assert_eq!(pools.empty_type_count(), 1);
+        pools.set_values::<u8>(Vec::new());
+        pools.trim_back::<u16>(1);
+        pools.extend([1u64]);
+        assert_eq!(pools.empty_type_count(), 3);
+        pools.remove_type::<u8>();
+        pools.empty_type::<u64>();
+        assert_eq!(pools.empty_type_count(), 3);
+        pools.type_pool_mut::<u32>().unwrap().values.push(2);
+        assert_eq!(pools.empty_type_count(), scanned(&pools));
+        pools.remove_empty();
+        assert_eq!(pools.empty_type_count(), 0);
+    }
+
+    #[test]
+    fn test_with_pool_notifies() {
+        let pushed = Rc::new(Cell::new(0));
+        let mut pools = TypePools::new();
+        let sum = pushed.clone();
+        pools.on_push::<u32, _>(move |value| sum.set(sum.get() + *value));
+        pools.push(1u8);
+
+        let mut pools = pools.with_pool(vec![1u32, 2]).with_pool(vec![3u32]);
+        assert_eq!(pushed.get(), 6);
+        pools.push(2u8);
+        pools.set_max_types(2);
+        pools.push(1u16);
+        assert!(pools.contains_type::<u8>());
+        assert!(!pools.contains_type::<u32>());
+    }
+
+    #[test]
+    fn test_set_values_notifies() {
+        let pushed = Rc::new(Cell::new(0));
+        let removed = Rc::new(Cell::new(0));
+        let emptied = Rc::new(Cell::new(0));
+        let mut pools = TypePools::new();
+        let sum = pushed.clone();
+        pools.on_push::<u32, _>(move |value| sum.set(sum.get() + *value));
+        let sum = removed.clone();
+        pools.on_remove::<u32, _>(move |value| sum.set(sum.get() + *value));
+        let counter = emptied.clone();
+        pools.on_empty::<u32, _>(move || counter.set(counter.get() + 1));
+        pools.enable_recording();
+
+        assert_eq!(pools.set_values(vec![1u32, 2]), None);
+        assert_eq!(pools.set_values(vec![3u32]), Some(vec![1, 2]));
+        assert_eq!((pushed.get(), removed.get(), emptied.get()), (6, 3, 0));
+        assert_eq!(pools.set_values(Vec::<u32>::new()), Some(vec![3]));
+        assert_eq!((pushed.get(), removed.get(), emptied.get()), (6, 6, 1));
+
+        let kinds: Vec<_> = pools.take_events().iter()
+            .map(|event| (event.kind, event.index))
+            .collect();
+        assert_eq!(kinds, vec![
+            (PoolEventKind::Push, 0), (PoolEventKind::Push, 1),
+            (PoolEventKind::Clear, 0), (PoolEventKind::Push, 0),
+            (PoolEventKind::Clear, 0),
+        ]);
+    }
+}